@@ -0,0 +1,407 @@
+use std::{cmp, net, sync};
+
+use anyhow::{Result, anyhow};
+
+use crate::{
+    HttpResponse, TemplateResolver,
+    query::{QueryResolver, Value},
+};
+
+/// A single check of an entry response. Version, status and headers come
+/// from the implicit `HTTP 200` line of the hurl response section.
+#[derive(Debug)]
+pub enum Check {
+    Version(hurl_core::ast::VersionValue),
+    Status(u64),
+    Header(Box<hurl_core::ast::KeyValue>),
+    Body(Box<hurl_core::ast::Body>),
+    Assert(Box<hurl_core::ast::Assert>),
+}
+
+impl Check {
+    /// once per entry, see `engine::Scenario::checks`
+    pub fn from_entry(entry: &hurl_core::ast::Entry) -> Vec<Self> {
+        let Some(response) = &entry.response else {
+            return Vec::new();
+        };
+
+        let mut checks = Vec::new();
+        if response.version.value != hurl_core::ast::VersionValue::VersionAny {
+            checks.push(Check::Version(response.version.value));
+        }
+        if let hurl_core::ast::StatusValue::Specific(status) = response.status.value {
+            checks.push(Check::Status(status));
+        }
+        checks.extend(
+            response
+                .headers
+                .iter()
+                .map(|key_value| Check::Header(Box::new(key_value.clone()))),
+        );
+        if let Some(body) = &response.body {
+            checks.push(Check::Body(Box::new(body.clone())));
+        }
+        checks.extend(
+            response
+                .asserts()
+                .iter()
+                .map(|assert| Check::Assert(Box::new(assert.clone()))),
+        );
+        checks
+    }
+
+    /// status checks are answered by curl directly, everything else needs
+    /// the response headers or body
+    pub fn needs_response(&self) -> bool {
+        !matches!(self, Check::Status(_))
+    }
+
+    pub fn label(&self, lines: &[&str]) -> String {
+        match self {
+            Check::Version(version) => format!("version {}", version),
+            Check::Status(status) => format!("status {}", status),
+            Check::Header(key_value) => format!("header {}: {}", key_value.key, key_value.value),
+            Check::Body(_) => "body".to_string(),
+            Check::Assert(assert) => lines
+                .get(assert.query.source_info.start.line - 1)
+                .map(|line| line.trim().to_string())
+                .unwrap_or_else(|| assert.query.value.identifier().to_string()),
+        }
+    }
+
+    /// Ok(false) means the check failed, errors mean it could not be evaluated
+    pub fn evaluate(
+        &self,
+        template_resolver: &TemplateResolver,
+        response: &HttpResponse,
+    ) -> Result<bool> {
+        match self {
+            Check::Version(version) => Ok(response.version == version.to_string()),
+            Check::Status(status) => Ok(response.status as u64 == *status),
+            Check::Header(key_value) => {
+                let key = template_resolver.resolve(&key_value.key)?;
+                let value = template_resolver.resolve(&key_value.value)?;
+                Ok(response.headers(&key).contains(&value.as_str()))
+            }
+            Check::Body(body) => {
+                let expected = template_resolver.resolve_bytes(&body.value)?;
+                if let hurl_core::ast::Bytes::Json(_) = body.value {
+                    // compare json values so that formatting differences do not matter
+                    let actual: serde_json::Value = serde_json::from_slice(&response.body)?;
+                    let expected: serde_json::Value = serde_json::from_slice(&expected)?;
+                    return Ok(actual == expected);
+                }
+                Ok(*response.body == *expected)
+            }
+            Check::Assert(assert) => {
+                let value = QueryResolver::new(template_resolver, response)
+                    .resolve_filtered(&assert.query, &assert.filters)?;
+                PredicateResolver::new(template_resolver).evaluate(&assert.predicate, value)
+            }
+        }
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(v) => Some(*v as f64),
+        Value::Float(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn equal(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Integer(a), Value::Integer(e)) => a == e,
+        (Value::Nodeset(a), Value::Integer(e)) => *a as i64 == *e,
+        (a, e) => match (as_number(a), as_number(e)) {
+            (Some(a), Some(e)) => a == e,
+            _ => a == e,
+        },
+    }
+}
+
+fn compare(actual: &Value, expected: &Value) -> Option<cmp::Ordering> {
+    match (actual, expected) {
+        (Value::Integer(a), Value::Integer(e)) => Some(a.cmp(e)),
+        (Value::String(a), Value::String(e)) => Some(a.cmp(e)),
+        (a, e) => as_number(a)?.partial_cmp(&as_number(e)?),
+    }
+}
+
+fn contains_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty()
+        || haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+}
+
+fn is_uuid(string: &str) -> bool {
+    let groups: Vec<_> = string.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn is_iso_date(string: &str) -> bool {
+    static ISO_DATE: sync::LazyLock<regex::Regex> = sync::LazyLock::new(|| {
+        regex::Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:\d{2})$")
+            .unwrap()
+    });
+    ISO_DATE.is_match(string)
+}
+
+struct PredicateResolver<'a> {
+    template_resolver: &'a TemplateResolver,
+}
+
+impl<'a> PredicateResolver<'a> {
+    fn new(template_resolver: &'a TemplateResolver) -> Self {
+        Self { template_resolver }
+    }
+
+    fn expected(&self, predicate_value: &hurl_core::ast::PredicateValue) -> Result<Value> {
+        Ok(match predicate_value {
+            hurl_core::ast::PredicateValue::Base64(v) => Value::Bytes(v.value.clone()),
+            hurl_core::ast::PredicateValue::Hex(v) => Value::Bytes(v.value.clone()),
+            hurl_core::ast::PredicateValue::Bool(v) => Value::Bool(*v),
+            hurl_core::ast::PredicateValue::Null => Value::Null,
+            hurl_core::ast::PredicateValue::Number(number) => match number {
+                hurl_core::ast::Number::Integer(v) => Value::Integer(v.as_i64()),
+                hurl_core::ast::Number::Float(v) => Value::Float(v.as_f64()),
+                hurl_core::ast::Number::BigInteger(v) => Value::String(v.clone()),
+            },
+            hurl_core::ast::PredicateValue::String(template) => {
+                Value::String(self.template_resolver.resolve(template)?)
+            }
+            hurl_core::ast::PredicateValue::MultilineString(multiline) => {
+                Value::String(self.template_resolver.resolve(&multiline.value())?)
+            }
+            hurl_core::ast::PredicateValue::Placeholder(placeholder) => self
+                .template_resolver
+                .resolve_placeholder_value(placeholder)?,
+            hurl_core::ast::PredicateValue::Regex(regex) => Value::String(regex.inner.to_string()),
+            hurl_core::ast::PredicateValue::File(file) => Value::Bytes(
+                self.template_resolver
+                    .resolve_file(&file.filename)?
+                    .to_vec(),
+            ),
+        })
+    }
+
+    fn regex(&self, predicate_value: &hurl_core::ast::PredicateValue) -> Result<regex::Regex> {
+        match predicate_value {
+            hurl_core::ast::PredicateValue::Regex(regex) => Ok(regex.inner.clone()),
+            v => match self.expected(v)? {
+                Value::String(v) => Ok(regex::Regex::new(&v)?),
+                v => Err(anyhow!("expected a regex, got {}", v.type_name())),
+            },
+        }
+    }
+
+    fn evaluate(
+        &self,
+        predicate: &hurl_core::ast::Predicate,
+        value: Option<Value>,
+    ) -> Result<bool> {
+        let passed = match (&predicate.predicate_func.value, value) {
+            (hurl_core::ast::PredicateFuncValue::Exist, value) => {
+                value.is_some_and(|v| v != Value::Nodeset(0))
+            }
+            // nothing to compare against, even a negated predicate fails
+            (_, None) => return Ok(false),
+            (hurl_core::ast::PredicateFuncValue::Equal { value: e, .. }, Some(v)) => {
+                equal(&v, &self.expected(e)?)
+            }
+            (hurl_core::ast::PredicateFuncValue::NotEqual { value: e, .. }, Some(v)) => {
+                !equal(&v, &self.expected(e)?)
+            }
+            (hurl_core::ast::PredicateFuncValue::GreaterThan { value: e, .. }, Some(v)) => {
+                compare(&v, &self.expected(e)?) == Some(cmp::Ordering::Greater)
+            }
+            (hurl_core::ast::PredicateFuncValue::GreaterThanOrEqual { value: e, .. }, Some(v)) => {
+                matches!(
+                    compare(&v, &self.expected(e)?),
+                    Some(cmp::Ordering::Greater | cmp::Ordering::Equal)
+                )
+            }
+            (hurl_core::ast::PredicateFuncValue::LessThan { value: e, .. }, Some(v)) => {
+                compare(&v, &self.expected(e)?) == Some(cmp::Ordering::Less)
+            }
+            (hurl_core::ast::PredicateFuncValue::LessThanOrEqual { value: e, .. }, Some(v)) => {
+                matches!(
+                    compare(&v, &self.expected(e)?),
+                    Some(cmp::Ordering::Less | cmp::Ordering::Equal)
+                )
+            }
+            (hurl_core::ast::PredicateFuncValue::StartWith { value: e, .. }, Some(v)) => {
+                match (v, self.expected(e)?) {
+                    (Value::String(v), Value::String(e)) => v.starts_with(&e),
+                    (Value::Bytes(v), Value::Bytes(e)) => v.starts_with(&e),
+                    _ => false,
+                }
+            }
+            (hurl_core::ast::PredicateFuncValue::EndWith { value: e, .. }, Some(v)) => {
+                match (v, self.expected(e)?) {
+                    (Value::String(v), Value::String(e)) => v.ends_with(&e),
+                    (Value::Bytes(v), Value::Bytes(e)) => v.ends_with(&e),
+                    _ => false,
+                }
+            }
+            (hurl_core::ast::PredicateFuncValue::Contain { value: e, .. }, Some(v)) => {
+                match (v, self.expected(e)?) {
+                    (Value::String(v), Value::String(e)) => v.contains(&e),
+                    (Value::Bytes(v), Value::Bytes(e)) => contains_bytes(&v, &e),
+                    (Value::List(v), e) => v.iter().any(|v| equal(v, &e)),
+                    _ => false,
+                }
+            }
+            (hurl_core::ast::PredicateFuncValue::Include { value: e, .. }, Some(v)) => {
+                let e = self.expected(e)?;
+                match v {
+                    Value::List(v) => v.iter().any(|v| equal(v, &e)),
+                    _ => false,
+                }
+            }
+            (hurl_core::ast::PredicateFuncValue::Match { value: e, .. }, Some(v)) => match v {
+                Value::String(v) => self.regex(e)?.is_match(&v),
+                _ => false,
+            },
+            (hurl_core::ast::PredicateFuncValue::IsBoolean, Some(v)) => {
+                matches!(v, Value::Bool(_))
+            }
+            (hurl_core::ast::PredicateFuncValue::IsCollection, Some(v)) => matches!(
+                v,
+                Value::List(_) | Value::Object(_) | Value::Bytes(_) | Value::Nodeset(_)
+            ),
+            (hurl_core::ast::PredicateFuncValue::IsEmpty, Some(v)) => match v {
+                Value::String(v) => v.is_empty(),
+                Value::Bytes(v) => v.is_empty(),
+                Value::List(v) => v.is_empty(),
+                Value::Object(v) => v.is_empty(),
+                Value::Nodeset(v) => v == 0,
+                _ => false,
+            },
+            (hurl_core::ast::PredicateFuncValue::IsFloat, Some(v)) => {
+                matches!(v, Value::Float(_))
+            }
+            (hurl_core::ast::PredicateFuncValue::IsInteger, Some(v)) => {
+                matches!(v, Value::Integer(_))
+            }
+            (hurl_core::ast::PredicateFuncValue::IsNumber, Some(v)) => {
+                matches!(v, Value::Integer(_) | Value::Float(_))
+            }
+            (hurl_core::ast::PredicateFuncValue::IsString, Some(v)) => {
+                matches!(v, Value::String(_))
+            }
+            (hurl_core::ast::PredicateFuncValue::IsList, Some(v)) => matches!(v, Value::List(_)),
+            (hurl_core::ast::PredicateFuncValue::IsObject, Some(v)) => {
+                matches!(v, Value::Object(_))
+            }
+            (hurl_core::ast::PredicateFuncValue::IsIpv4, Some(v)) => {
+                matches!(v, Value::String(v) if v.parse::<net::Ipv4Addr>().is_ok())
+            }
+            (hurl_core::ast::PredicateFuncValue::IsIpv6, Some(v)) => {
+                matches!(v, Value::String(v) if v.parse::<net::Ipv6Addr>().is_ok())
+            }
+            (hurl_core::ast::PredicateFuncValue::IsUuid, Some(v)) => {
+                matches!(v, Value::String(v) if is_uuid(&v))
+            }
+            (hurl_core::ast::PredicateFuncValue::IsIsoDate, Some(v)) => {
+                matches!(v, Value::String(v) if is_iso_date(&v))
+            }
+            // values are never dates, there are no date filters
+            (hurl_core::ast::PredicateFuncValue::IsDate, Some(_)) => false,
+        };
+        Ok(passed != predicate.not)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_numbers() {
+        assert!(equal(&Value::Integer(1), &Value::Integer(1)));
+        assert!(!equal(&Value::Integer(1), &Value::Integer(2)));
+        assert!(equal(&Value::Integer(1), &Value::Float(1.0)));
+        assert!(equal(&Value::Float(2.0), &Value::Integer(2)));
+        assert!(!equal(&Value::Float(1.5), &Value::Integer(1)));
+        assert!(equal(&Value::Nodeset(3), &Value::Integer(3)));
+        // integers beyond f64 precision are compared exactly
+        assert!(!equal(
+            &Value::Integer(i64::MAX),
+            &Value::Integer(i64::MAX - 1)
+        ));
+
+        assert!(!equal(&Value::String("1".to_string()), &Value::Integer(1)));
+        assert!(equal(
+            &Value::String("a".to_string()),
+            &Value::String("a".to_string())
+        ));
+        assert!(equal(&Value::Null, &Value::Null));
+        assert!(!equal(&Value::Float(f64::NAN), &Value::Float(f64::NAN)));
+    }
+
+    #[test]
+    fn compare_values() {
+        let compare_integers = |a, e| compare(&Value::Integer(a), &Value::Integer(e));
+        assert_eq!(compare_integers(2, 1), Some(cmp::Ordering::Greater));
+        assert_eq!(compare_integers(1, 1), Some(cmp::Ordering::Equal));
+        assert_eq!(
+            compare(&Value::Integer(1), &Value::Float(1.5)),
+            Some(cmp::Ordering::Less)
+        );
+        assert_eq!(
+            compare(&Value::Float(2.5), &Value::Integer(2)),
+            Some(cmp::Ordering::Greater)
+        );
+        assert_eq!(
+            compare(
+                &Value::String("b".to_string()),
+                &Value::String("a".to_string())
+            ),
+            Some(cmp::Ordering::Greater)
+        );
+
+        assert_eq!(
+            compare(&Value::String("2".to_string()), &Value::Integer(1)),
+            None
+        );
+        assert_eq!(compare(&Value::Float(f64::NAN), &Value::Integer(1)), None);
+        assert_eq!(compare(&Value::Null, &Value::Null), None);
+    }
+
+    #[test]
+    fn bytes() {
+        assert!(contains_bytes(b"hello", b"ell"));
+        assert!(contains_bytes(b"hello", b""));
+        assert!(contains_bytes(b"", b""));
+        assert!(!contains_bytes(b"hello", b"hellos"));
+        assert!(!contains_bytes(b"", b"a"));
+    }
+
+    #[test]
+    fn uuid() {
+        assert!(is_uuid("123e4567-e89b-12d3-a456-426614174000"));
+        assert!(is_uuid("123E4567-E89B-12D3-A456-426614174000"));
+        assert!(!is_uuid("123e4567e89b12d3a456426614174000"));
+        assert!(!is_uuid("123e4567-e89b-12d3-a456-42661417400"));
+        assert!(!is_uuid("123e4567-e89b-12d3-a456-42661417400g"));
+        assert!(!is_uuid("123e4567-e89b-12d3-a456-426614174000-0"));
+    }
+
+    #[test]
+    fn iso_date() {
+        assert!(is_iso_date("2024-01-31T12:00:00Z"));
+        assert!(is_iso_date("2024-01-31T12:00:00.123Z"));
+        assert!(is_iso_date("2024-01-31T12:00:00+02:00"));
+        assert!(!is_iso_date("2024-01-31"));
+        assert!(!is_iso_date("2024-01-31 12:00:00Z"));
+        assert!(!is_iso_date("2024-01-31T12:00:00"));
+        assert!(!is_iso_date(" 2024-01-31T12:00:00Z"));
+    }
+}
@@ -0,0 +1,564 @@
+use std::{
+    fmt, ops,
+    sync::{self, atomic},
+    thread, time,
+};
+
+use anyhow::{Result, anyhow};
+
+use crate::{
+    Collector, Endpoint, HttpResponse, Response, Sample, TemplateResolver, WorkerStatistics,
+    assert, evaluate_response, options, request_log, schedule,
+};
+
+/// longest a worker waits, for its transfers or for a request to be due,
+/// before checking `stop`
+const WAIT_MAX: time::Duration = time::Duration::from_millis(100);
+
+/// how the parallel sessions are run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    /// a thread per session, each one with a blocking client
+    Threads,
+    /// a few threads, each one running many sessions whose transfers share
+    /// curl's multi interface
+    Multi,
+}
+
+impl Engine {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "threads" => Ok(Self::Threads),
+            "multi" => Ok(Self::Multi),
+            _ => Err(anyhow!("engine {}: expected threads or multi", name)),
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Threads => write!(f, "threads"),
+            Self::Multi => write!(f, "multi"),
+        }
+    }
+}
+
+/// what is done with the response bodies, a body is always buffered when the
+/// captures or asserts of its entry need it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseBody {
+    /// only its size is counted
+    Discard,
+    /// buffered like a client would
+    Buffer,
+    /// buffered, the last N of every entry are kept to be printed after the run
+    Keep(usize),
+}
+
+impl ResponseBody {
+    /// `discard`, `buffer` or `keep=N`
+    pub fn parse(mode: &str) -> Result<Self> {
+        match mode {
+            "discard" => Ok(Self::Discard),
+            "buffer" => Ok(Self::Buffer),
+            _ => match mode.strip_prefix("keep=").map(str::parse) {
+                Some(Ok(count)) if count > 0 => Ok(Self::Keep(count)),
+                _ => Err(anyhow!(
+                    "response body {}: expected discard, buffer or keep=N",
+                    mode
+                )),
+            },
+        }
+    }
+
+    pub fn kept_body_count(self) -> usize {
+        match self {
+            Self::Keep(count) => count,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for ResponseBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Discard => write!(f, "discard"),
+            Self::Buffer => write!(f, "buffer"),
+            Self::Keep(count) => write!(f, "keep={}", count),
+        }
+    }
+}
+
+/// what every session runs
+pub struct Scenario {
+    pub entries: Vec<hurl_core::ast::Entry>,
+    /// of each entry, see `assert::Check::from_entry`
+    pub checks: Vec<Vec<assert::Check>>,
+    /// whether each entry needs its response, see `crate::collects`
+    pub collects: Vec<bool>,
+    pub variables: TemplateResolver,
+    /// options of the command line
+    pub defaults: options::EntryOptions,
+    pub response_body: ResponseBody,
+    pub schedule: Option<schedule::Schedule>,
+}
+
+/// when the workers stop: at the end of the run, or once there have been
+/// `max_errors` errors. The measured window is from its creation
+/// to the stop, the responses received later are not tracked
+pub struct Stop {
+    start: time::Instant,
+    stop: sync::OnceLock<time::Instant>,
+    stopped: atomic::AtomicBool,
+    error_count: atomic::AtomicUsize,
+    max_errors: Option<usize>,
+}
+
+impl Stop {
+    pub fn new(max_errors: Option<usize>) -> Self {
+        Self {
+            start: time::Instant::now(),
+            stop: sync::OnceLock::new(),
+            stopped: atomic::AtomicBool::new(false),
+            error_count: atomic::AtomicUsize::new(0),
+            max_errors,
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stop.get_or_init(time::Instant::now);
+        self.stopped.store(true, atomic::Ordering::Relaxed);
+    }
+
+    pub fn start(&self) -> time::Instant {
+        self.start
+    }
+
+    /// measured window, up to now while running
+    pub fn window(&self) -> time::Duration {
+        match self.stop.get() {
+            Some(stop) => *stop - self.start,
+            None => self.start.elapsed(),
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(atomic::Ordering::Relaxed)
+    }
+
+    /// whether the run was stopped early by `max_errors`
+    pub fn max_errors_reached(&self) -> bool {
+        self.max_errors.is_some_and(|max_errors| {
+            self.error_count.load(atomic::Ordering::Relaxed) >= max_errors
+        })
+    }
+
+    fn track_error(&self) {
+        self.error_count.fetch_add(1, atomic::Ordering::Relaxed);
+        if self.max_errors_reached() {
+            self.stop();
+        }
+    }
+}
+
+/// what a session needs from the engine running it
+pub enum Step {
+    /// the client is set up, it is to be performed then passed to `Session::complete`
+    Perform,
+    /// nothing to do until then
+    Wait(time::Instant),
+}
+
+/// a virtual user running the iterations of the scenario, one request at a
+/// time. It never blocks, waiting is left to the engine
+pub struct Session<'a> {
+    scenario: &'a Scenario,
+    stop: &'a Stop,
+    template_resolver: TemplateResolver,
+    iteration: Option<Iteration>,
+    log: Option<request_log::Logger>,
+}
+
+struct Iteration {
+    /// intended start, with `--rate`
+    due: Option<time::Instant>,
+    /// actual start and delay since the intended start, once started
+    started: Option<(time::Instant, time::Duration)>,
+    /// index of the current entry
+    index: usize,
+    entry: Option<EntryRun>,
+}
+
+/// repeats and retries of the current entry
+struct EntryRun {
+    options: options::EntryOptions,
+    count: usize,
+    attempt: usize,
+    /// after `delay`, plus `retry_interval` between attempts
+    next_send: time::Instant,
+    /// when the pending request was sent
+    sent: Option<time::Instant>,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(scenario: &'a Scenario, stop: &'a Stop, log: Option<request_log::Logger>) -> Self {
+        Self {
+            scenario,
+            stop,
+            template_resolver: scenario.variables.clone(),
+            iteration: None,
+            log,
+        }
+    }
+
+    /// sets up `client` for the next request, unless there is one to wait for
+    pub fn next(
+        &mut self,
+        client: &mut curl::easy::Easy2<Collector>,
+        statistics: &mut WorkerStatistics,
+    ) -> Result<Step> {
+        let scenario = self.scenario;
+        loop {
+            let now = time::Instant::now();
+            let Some(iteration) = &mut self.iteration else {
+                let due = scenario.schedule.as_ref().map(|schedule| {
+                    loop {
+                        match schedule.claim() {
+                            schedule::Slot::Started { due } => break due,
+                            schedule::Slot::Dropped => statistics.track(Sample::Dropped),
+                        }
+                    }
+                });
+                // every iteration starts from the command line variables,
+                // captures of the previous iteration are dropped
+                self.template_resolver = scenario.variables.clone();
+                self.iteration = Some(Iteration {
+                    due,
+                    started: None,
+                    index: 0,
+                    entry: None,
+                });
+                continue;
+            };
+            let (instant, delay) = match iteration.started {
+                Some(started) => started,
+                None => {
+                    if let Some(due) = iteration.due.filter(|due| *due > now) {
+                        return Ok(Step::Wait(due));
+                    }
+                    let delay = iteration
+                        .due
+                        .map(|due| now.saturating_duration_since(due))
+                        .unwrap_or_default();
+                    *iteration.started.insert((now, delay))
+                }
+            };
+            let Some(entry) = scenario.entries.get(iteration.index) else {
+                statistics.track(Sample::Iteration {
+                    duration: now - instant,
+                    delay,
+                });
+                self.iteration = None;
+                continue;
+            };
+            let Some(run) = &mut iteration.entry else {
+                let options = options::EntryOptions::new(
+                    &scenario.defaults,
+                    &mut self.template_resolver,
+                    entry,
+                )?;
+                if options.skip {
+                    iteration.index += 1;
+                } else {
+                    iteration.entry = Some(EntryRun {
+                        next_send: now + options.delay,
+                        options,
+                        count: 0,
+                        attempt: 0,
+                        sent: None,
+                    });
+                }
+                continue;
+            };
+            if !run.options.repeat.contains(run.count) {
+                iteration.index += 1;
+                iteration.entry = None;
+                continue;
+            }
+            if run.next_send > now {
+                return Ok(Step::Wait(run.next_send));
+            }
+
+            Endpoint::new(&self.template_resolver, entry, &run.options)?.prepare(
+                client,
+                &run.options,
+                self.scenario.response_body != ResponseBody::Discard
+                    || self.scenario.collects[iteration.index],
+            )?;
+            run.sent = Some(now);
+            return Ok(Step::Perform);
+        }
+    }
+
+    /// handles the response of the request set up by `next`, the entry is
+    /// sent again until its checks pass or its retries are exhausted, only
+    /// the last attempt is reported. A transport error or a failed capture is
+    /// counted and ends the iteration, as the following entries may depend on
+    /// its captures
+    pub fn complete(
+        &mut self,
+        client: &mut curl::easy::Easy2<Collector>,
+        result: Result<(), curl::Error>,
+        statistics: &mut WorkerStatistics,
+    ) -> Result<()> {
+        // outside of the measured window
+        if self.stop.is_stopped() {
+            return Ok(());
+        }
+        let Some(Iteration {
+            started: Some((_, delay)),
+            index,
+            entry: Some(run),
+            ..
+        }) = &mut self.iteration
+        else {
+            return Err(anyhow!("no request was set up"));
+        };
+        let sent = run
+            .sent
+            .take()
+            .ok_or_else(|| anyhow!("no request was set up"))?;
+        let entry = &self.scenario.entries[*index];
+
+        let now = time::Instant::now();
+        let response = match result {
+            Ok(()) => HttpResponse::new(client, now - sent).map(|http_response| {
+                // e.g. jsonpath on a html body
+                let response = evaluate_response(
+                    &mut self.template_resolver,
+                    entry,
+                    &self.scenario.checks[*index],
+                    &http_response,
+                )
+                .map_err(|_| CAPTURE_ERROR_KIND)?;
+                Ok(match self.scenario.response_body {
+                    ResponseBody::Keep(_) => Response {
+                        body: Some(http_response.body),
+                        ..response
+                    },
+                    _ => response,
+                })
+            }),
+            Err(err) => Ok(Err(error_kind(&err))),
+        };
+        if let Some(log) = &self.log {
+            let error = match &response {
+                Ok(Err(kind)) => Some(*kind),
+                _ => None,
+            };
+            log.log(client, *index + 1, now - sent, error);
+        }
+        let passed = matches!(&response, Ok(Ok(response)) if response.failed_asserts.is_empty());
+        if !passed
+            && run
+                .options
+                .retry
+                .is_some_and(|retry| retry.contains(run.attempt))
+        {
+            run.attempt += 1;
+            run.next_send = now + run.options.retry_interval + run.options.delay;
+            return Ok(());
+        }
+
+        match response? {
+            Ok(response) => {
+                statistics.track(Sample::Entry {
+                    index: *index,
+                    response,
+                    delay: *delay,
+                });
+                run.count += 1;
+                run.attempt = 0;
+                run.next_send = now + run.options.delay;
+            }
+            Err(kind) => {
+                statistics.track(Sample::Error {
+                    index: *index,
+                    kind,
+                });
+                self.stop.track_error();
+                self.iteration = None;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// error kind of a response whose captures could not be evaluated
+const CAPTURE_ERROR_KIND: &str = "capture";
+
+/// kind of a transport error, counted next to the statuses
+fn error_kind(err: &curl::Error) -> &'static str {
+    if err.is_couldnt_connect() {
+        "connection refused"
+    } else if err.is_operation_timedout() {
+        "timeout"
+    } else if err.is_recv_error() || err.is_send_error() || err.is_got_nothing() {
+        "connection reset"
+    } else if err.is_couldnt_resolve_host() || err.is_couldnt_resolve_proxy() {
+        "dns"
+    } else if err.is_ssl_connect_error()
+        || err.is_peer_failed_verification()
+        || err.is_ssl_certproblem()
+        || err.is_ssl_cipher()
+        || err.is_ssl_cacert()
+        || err.is_ssl_cacert_badfile()
+        || err.is_ssl_issuer_error()
+    {
+        "tls"
+    } else {
+        "other"
+    }
+}
+
+/// runs `session_count` sessions until `stop` is set, a single one with the
+/// threads engine
+pub fn run_worker(
+    engine: Engine,
+    sessions: ops::Range<usize>,
+    max_streams: Option<usize>,
+    scenario: &Scenario,
+    stop: &Stop,
+    log: Option<request_log::Logger>,
+    mut statistics: WorkerStatistics,
+) -> Result<()> {
+    // numbered from 1 in the request log
+    let mut sessions = sessions.map(|index| {
+        Session::new(
+            scenario,
+            stop,
+            log.as_ref().map(|log| log.with_worker(index + 1)),
+        )
+    });
+    let result = match engine {
+        Engine::Threads => run_blocking(
+            sessions
+                .next()
+                .ok_or_else(|| anyhow!("no session to run"))?,
+            stop,
+            &mut statistics,
+        ),
+        Engine::Multi => run_multi(sessions.collect(), max_streams, stop, &mut statistics),
+    };
+    statistics.finish();
+    result
+}
+
+fn run_blocking(
+    mut session: Session,
+    stop: &Stop,
+    statistics: &mut WorkerStatistics,
+) -> Result<()> {
+    let mut client = curl::easy::Easy2::new(Collector::default());
+    while !stop.is_stopped() {
+        match session.next(&mut client, statistics)? {
+            Step::Wait(instant) => thread::sleep(
+                instant
+                    .saturating_duration_since(time::Instant::now())
+                    .min(WAIT_MAX),
+            ),
+            Step::Perform => {
+                let result = client.perform();
+                session.complete(&mut client, result, statistics)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// a session of a multi worker, its client belongs to the multi handle while
+/// its request is performed
+struct MultiSession<'a> {
+    session: Session<'a>,
+    client: Option<curl::easy::Easy2<Collector>>,
+    handle: Option<curl::multi::Easy2Handle<Collector>>,
+    wait: Option<time::Instant>,
+}
+
+fn run_multi(
+    sessions: Vec<Session>,
+    max_streams: Option<usize>,
+    stop: &Stop,
+    statistics: &mut WorkerStatistics,
+) -> Result<()> {
+    let mut multi = curl::multi::Multi::new();
+    multi.pipelining(false, true)?;
+    if let Some(max_streams) = max_streams {
+        multi.set_max_concurrent_streams(max_streams)?;
+    }
+    let mut sessions: Vec<_> = sessions
+        .into_iter()
+        .map(|session| MultiSession {
+            session,
+            client: Some(curl::easy::Easy2::new(Collector::default())),
+            handle: None,
+            wait: None,
+        })
+        .collect();
+    let mut completed = Vec::new();
+    while !stop.is_stopped() {
+        let now = time::Instant::now();
+        let mut wake = now + WAIT_MAX;
+        for (token, session) in sessions.iter_mut().enumerate() {
+            // without a client its request is being performed
+            let Some(client) = &mut session.client else {
+                continue;
+            };
+            if let Some(wait) = session.wait.filter(|wait| *wait > now) {
+                wake = wake.min(wait);
+                continue;
+            }
+            match session.session.next(client, statistics)? {
+                Step::Wait(instant) => {
+                    session.wait = Some(instant);
+                    wake = wake.min(instant);
+                }
+                Step::Perform => {
+                    if let Some(mut client) = session.client.take() {
+                        // a new request waits for a HTTP/2 connection to be
+                        // multiplexed on rather than opening another one
+                        client.pipewait(true)?;
+                        let mut handle = multi.add2(client)?;
+                        handle.set_token(token)?;
+                        session.handle = Some(handle);
+                    }
+                }
+            }
+        }
+
+        multi.perform()?;
+        multi.messages(|message| {
+            if let (Ok(token), Some(result)) = (message.token(), message.result()) {
+                completed.push((token, result));
+            }
+        });
+        let timeout = wake.saturating_duration_since(time::Instant::now());
+        if sessions.iter().all(|session| session.handle.is_none()) {
+            // curl returns at once without a transfer, every session waits
+            thread::sleep(timeout);
+        } else if completed.is_empty() {
+            multi.wait(&mut [], timeout)?;
+        }
+        for (token, result) in completed.drain(..) {
+            let session = &mut sessions[token];
+            if let Some(handle) = session.handle.take() {
+                let mut client = multi.remove2(handle)?;
+                session.session.complete(&mut client, result, statistics)?;
+                session.client = Some(client);
+                session.wait = None;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::{path, sync::atomic, time};
+
+use anyhow::Result;
+
+use crate::{TemplateResolver, query::url_encode};
+
+/// `key=value&...` encoding of a `[FormParams]` or `[QueryStringParams]` section
+pub fn encode_params(
+    template_resolver: &TemplateResolver,
+    params: &[hurl_core::ast::KeyValue],
+) -> Result<String> {
+    let pairs = params
+        .iter()
+        .map(|param| {
+            Ok(format!(
+                "{}={}",
+                url_encode(&template_resolver.resolve(&param.key)?),
+                url_encode(&template_resolver.resolve(&param.value)?)
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(pairs.join("&"))
+}
+
+/// a boundary unique to every request, so that it can not collide with a
+/// previous request body that happens to contain it
+pub fn multipart_boundary() -> String {
+    static COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
+
+    let nanos = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let count = COUNTER.fetch_add(1, atomic::Ordering::Relaxed);
+    format!("----hurlbench{:x}{:x}", nanos, count)
+}
+
+/// same guesses as hurl, by file extension
+fn guess_content_type(filename: &str) -> &'static str {
+    let extension = path::Path::new(filename)
+        .extension()
+        .and_then(|v| v.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "gif" => "image/gif",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "svg" => "image/svg+xml",
+        "txt" => "text/plain",
+        "htm" | "html" => "text/html",
+        "pdf" => "application/pdf",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+/// `multipart/form-data` body of a `[MultipartFormData]` section
+pub fn encode_multipart(
+    template_resolver: &TemplateResolver,
+    params: &[hurl_core::ast::MultipartParam],
+    boundary: &str,
+) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    for param in params {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        match param {
+            hurl_core::ast::MultipartParam::Param(key_value) => {
+                body.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                        template_resolver.resolve(&key_value.key)?
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(template_resolver.resolve(&key_value.value)?.as_bytes());
+            }
+            hurl_core::ast::MultipartParam::FilenameParam(filename_param) => {
+                let filename = template_resolver.resolve(&filename_param.value.filename)?;
+                let content_type = match &filename_param.value.content_type {
+                    Some(content_type) => template_resolver.resolve(content_type)?,
+                    None => guess_content_type(&filename).to_string(),
+                };
+                let basename = path::Path::new(&filename)
+                    .file_name()
+                    .and_then(|v| v.to_str())
+                    .unwrap_or(&filename);
+                body.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                        template_resolver.resolve(&filename_param.key)?,
+                        basename,
+                        content_type
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(
+                    &template_resolver.resolve_file(&filename_param.value.filename)?,
+                );
+            }
+        }
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    fn request(hurl: &str) -> hurl_core::ast::Request {
+        hurl_core::parser::parse_hurl_file(hurl)
+            .unwrap()
+            .entries
+            .remove(0)
+            .request
+    }
+
+    #[test]
+    fn params() {
+        let request =
+            request("POST http://localhost\n[FormParams]\nname: a b\nq: x&y=é\nn: {{n}}\n");
+        let template_resolver = TemplateResolver::new(
+            vec![crate::Variable::parse("n=1").unwrap()],
+            path::PathBuf::new(),
+        );
+        assert_eq!(
+            encode_params(&template_resolver, request.form_params()).unwrap(),
+            "name=a%20b&q=x%26y%3D%C3%A9&n=1"
+        );
+    }
+
+    #[test]
+    fn multipart() {
+        let root = env::temp_dir().join(format!("hurlbench-form-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("data.json"), "{\"a\":1}").unwrap();
+        let request = request(
+            "POST http://localhost\n[MultipartFormData]\nfield: value\nupload: file,data.json;\n",
+        );
+        let template_resolver = TemplateResolver::new(Vec::new(), root.clone());
+        let body = encode_multipart(
+            &template_resolver,
+            request.multipart_form_data(),
+            "BOUNDARY",
+        )
+        .unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "--BOUNDARY\r\n\
+             Content-Disposition: form-data; name=\"field\"\r\n\
+             \r\n\
+             value\r\n\
+             --BOUNDARY\r\n\
+             Content-Disposition: form-data; name=\"upload\"; filename=\"data.json\"\r\n\
+             Content-Type: application/json\r\n\
+             \r\n\
+             {\"a\":1}\r\n\
+             --BOUNDARY--\r\n"
+        );
+    }
+
+    #[test]
+    fn content_type() {
+        assert_eq!(guess_content_type("a/b.PNG"), "image/png");
+        assert_eq!(guess_content_type("b.jpeg"), "image/jpeg");
+        assert_eq!(guess_content_type("b.txt"), "text/plain");
+        assert_eq!(guess_content_type("b.tar.gz"), "application/octet-stream");
+        assert_eq!(guess_content_type("b"), "application/octet-stream");
+    }
+}
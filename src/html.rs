@@ -0,0 +1,451 @@
+use std::fmt::{self, Write};
+
+use crate::{Latency, PERCENTILES, ResponseCount, new_date, report::Report, series};
+
+const WIDTH: f64 = 760.0;
+const HEIGHT: f64 = 220.0;
+/// room for the axis labels
+const LEFT: f64 = 80.0;
+const RIGHT: f64 = 10.0;
+const TOP: f64 = 10.0;
+const BOTTOM: f64 = 30.0;
+const TICK_COUNT: usize = 4;
+const HISTOGRAM_BIN_COUNT: usize = 40;
+const COLORS: [&str; 4] = ["#1f77b4", "#ff7f0e", "#d62728", "#7f7f7f"];
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }
+svg text { font-size: 11px; fill: #444; }
+svg text.y { text-anchor: end; }
+svg text.x { text-anchor: middle; }
+svg line.grid { stroke: #ddd; }
+.legend span { margin-right: 1em; }
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// a few significant digits, whatever the magnitude
+fn format_value(value: f64) -> String {
+    if value == 0.0 {
+        "0".to_string()
+    } else if value >= 100.0 {
+        format!("{:.0}", value)
+    } else if value >= 1.0 {
+        format!("{:.1}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+fn milliseconds(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn write_rows<N: ToString, V: ToString>(
+    out: &mut String,
+    rows: impl IntoIterator<Item = (N, V)>,
+) -> fmt::Result {
+    writeln!(out, "<table>")?;
+    for (name, value) in rows {
+        writeln!(
+            out,
+            "<tr><th>{}</th><td class=\"number\">{}</td></tr>",
+            escape(&name.to_string()),
+            escape(&value.to_string())
+        )?;
+    }
+    writeln!(out, "</table>")
+}
+
+fn write_count<K: ToString>(
+    out: &mut String,
+    title: &str,
+    count: &ResponseCount<K>,
+) -> fmt::Result {
+    writeln!(out, "<h3>{}</h3>", title)?;
+    if count.0.is_empty() {
+        return writeln!(out, "<p>none</p>");
+    }
+    write_rows(out, count.0.iter().map(|(key, n)| (key.to_string(), n)))
+}
+
+fn write_legend(out: &mut String, names: &[&str]) -> fmt::Result {
+    write!(out, "<p class=\"legend\">")?;
+    for (name, color) in names.iter().zip(COLORS.iter().cycle()) {
+        write!(
+            out,
+            "<span style=\"color: {}\">&#9632; {}</span>",
+            color,
+            escape(name)
+        )?;
+    }
+    writeln!(out, "</p>")
+}
+
+/// axes and horizontal grid, `to_x` and `to_y` map values to the chart
+fn write_axes(
+    out: &mut String,
+    x_labels: &[(f64, String)],
+    y_max: f64,
+    y_unit: &str,
+    to_y: impl Fn(f64) -> f64,
+) -> fmt::Result {
+    for tick in 0..=TICK_COUNT {
+        let value = y_max * tick as f64 / TICK_COUNT as f64;
+        writeln!(
+            out,
+            "<line class=\"grid\" x1=\"{}\" y1=\"{:.1}\" x2=\"{}\" y2=\"{:.1}\"/>\
+             <text class=\"y\" x=\"{}\" y=\"{:.1}\">{} {}</text>",
+            LEFT,
+            to_y(value),
+            WIDTH - RIGHT,
+            to_y(value),
+            LEFT - 4.0,
+            to_y(value) + 4.0,
+            format_value(value),
+            y_unit
+        )?;
+    }
+    for (x, label) in x_labels {
+        writeln!(
+            out,
+            "<text class=\"x\" x=\"{:.1}\" y=\"{}\">{}</text>",
+            x,
+            HEIGHT - BOTTOM + 16.0,
+            escape(label)
+        )?;
+    }
+    Ok(())
+}
+
+/// name and points of a line, a point without `y` is a gap
+type Line<'a> = (&'a str, Vec<(f64, Option<f64>)>);
+
+/// lines over the measured window
+fn write_line_chart(out: &mut String, window: f64, y_unit: &str, lines: &[Line]) -> fmt::Result {
+    let y_max = lines
+        .iter()
+        .flat_map(|(_, points)| points.iter().filter_map(|(_, y)| *y))
+        .fold(0.0, f64::max);
+    let y_max = if y_max > 0.0 { y_max } else { 1.0 };
+    let window = window.max(f64::EPSILON);
+    let to_x = |x: f64| LEFT + x / window * (WIDTH - LEFT - RIGHT);
+    let to_y = |y: f64| TOP + (1.0 - y / y_max) * (HEIGHT - TOP - BOTTOM);
+
+    write_legend(
+        out,
+        &lines.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+    )?;
+    writeln!(
+        out,
+        "<svg viewBox=\"0 0 {w} {h}\" width=\"{w}\" height=\"{h}\">",
+        w = WIDTH,
+        h = HEIGHT
+    )?;
+    let x_labels = (0..=TICK_COUNT)
+        .map(|tick| {
+            let x = window * tick as f64 / TICK_COUNT as f64;
+            (to_x(x), format!("{}s", format_value(x)))
+        })
+        .collect::<Vec<_>>();
+    write_axes(out, &x_labels, y_max, y_unit, to_y)?;
+    for ((_, points), color) in lines.iter().zip(COLORS.iter().cycle()) {
+        for run in points.split(|(_, y)| y.is_none()) {
+            let coordinates = run
+                .iter()
+                .filter_map(|(x, y)| Some((to_x(*x), to_y((*y)?))))
+                .collect::<Vec<_>>();
+            if let [(x, y)] = coordinates[..] {
+                writeln!(
+                    out,
+                    "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2\" fill=\"{}\"/>",
+                    x, y, color
+                )?;
+            } else if !coordinates.is_empty() {
+                writeln!(
+                    out,
+                    "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>",
+                    color,
+                    coordinates
+                        .iter()
+                        .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )?;
+            }
+        }
+    }
+    writeln!(out, "</svg>")
+}
+
+/// response count by latency, on a log scale from the fastest to the slowest
+fn write_histogram(out: &mut String, latency: &Latency) -> fmt::Result {
+    if latency.is_empty() {
+        return writeln!(out, "<p>no response</p>");
+    }
+    // microseconds
+    let low = latency.0.min().max(1) as f64;
+    let high = (latency.0.max() as f64).max(low + 1.0);
+    let ratio = (high / low).powf(1.0 / HISTOGRAM_BIN_COUNT as f64);
+    let mut bins = [0u64; HISTOGRAM_BIN_COUNT];
+    for value in latency.0.iter_recorded() {
+        let bin = ((value.value_iterated_to() as f64).max(low) / low).ln() / ratio.ln();
+        bins[(bin as usize).min(HISTOGRAM_BIN_COUNT - 1)] += value.count_at_value();
+    }
+    // whole counts on the grid
+    let y_max = bins
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or_default()
+        .max(1)
+        .div_ceil(TICK_COUNT as u64)
+        * TICK_COUNT as u64;
+    let y_max = y_max as f64;
+    let bin_width = (WIDTH - LEFT - RIGHT) / HISTOGRAM_BIN_COUNT as f64;
+    let to_y = |y: f64| TOP + (1.0 - y / y_max) * (HEIGHT - TOP - BOTTOM);
+
+    writeln!(
+        out,
+        "<svg viewBox=\"0 0 {w} {h}\" width=\"{w}\" height=\"{h}\">",
+        w = WIDTH,
+        h = HEIGHT
+    )?;
+    let x_labels = (0..=TICK_COUNT)
+        .map(|tick| {
+            let bin = HISTOGRAM_BIN_COUNT * tick / TICK_COUNT;
+            let value = low * ratio.powi(bin as i32) / 1000.0;
+            (
+                LEFT + bin as f64 * bin_width,
+                format!("{}ms", format_value(value)),
+            )
+        })
+        .collect::<Vec<_>>();
+    write_axes(out, &x_labels, y_max, "", to_y)?;
+    for (index, count) in bins.iter().enumerate() {
+        if *count == 0 {
+            continue;
+        }
+        writeln!(
+            out,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\">\
+             <title>{} responses</title></rect>",
+            LEFT + index as f64 * bin_width + 1.0,
+            to_y(*count as f64),
+            bin_width - 2.0,
+            to_y(0.0) - to_y(*count as f64),
+            COLORS[0],
+            count
+        )?;
+    }
+    writeln!(out, "</svg>")
+}
+
+/// middle of an interval, where its value is drawn
+fn middle(point: &series::Point) -> f64 {
+    (point.offset + point.duration / 2).as_secs_f64()
+}
+
+/// the report as a single page without external resources, the charts are
+/// inline SVG
+pub fn render(report: &Report) -> Result<String, fmt::Error> {
+    let summary = report.summary;
+    let requests = &summary.requests;
+    let window = summary.window.as_secs_f64();
+    let mut out = String::new();
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(
+        out,
+        "<title>hurlbench {}</title>",
+        escape(&report.cmd_args.filepath)
+    )?;
+    writeln!(out, "<style>{}</style></head><body>", STYLE)?;
+    writeln!(
+        out,
+        "<h1>hurlbench {}</h1>",
+        escape(&report.cmd_args.filepath)
+    )?;
+    writeln!(
+        out,
+        "<p>from {} to {}</p>",
+        new_date(report.start),
+        new_date(report.start + summary.window)
+    )?;
+
+    writeln!(out, "<h2>Summary</h2>")?;
+    let mut rows = vec![
+        ("duration", format!("{:.3}s", window)),
+        (
+            "requests",
+            format!(
+                "{} ({:.1}/s)",
+                requests.request_count,
+                summary.per_second(requests.request_count)
+            ),
+        ),
+        (
+            "iterations",
+            format!(
+                "{} ({:.1}/s)",
+                summary.iteration_count,
+                summary.per_second(summary.iteration_count)
+            ),
+        ),
+        (
+            "errors",
+            format!(
+                "{} ({:.2}%)",
+                summary.error_count(),
+                summary.error_rate() * 100.0
+            ),
+        ),
+        ("bytes received", format!("{}B", requests.total_body_size)),
+    ];
+    if !requests.latency.is_empty() {
+        rows.push((
+            "mean",
+            format!("{:.3}ms", milliseconds(requests.latency.mean())),
+        ));
+        rows.push((
+            "stddev",
+            format!("{:.3}ms", milliseconds(requests.latency.stddev())),
+        ));
+    }
+    write_rows(&mut out, rows)?;
+
+    if !report.outcomes.is_empty() {
+        writeln!(out, "<h2>Thresholds</h2>")?;
+        write_rows(
+            &mut out,
+            report
+                .outcomes
+                .iter()
+                .map(|outcome| (outcome.threshold, outcome.result())),
+        )?;
+    }
+
+    writeln!(out, "<h2>Throughput</h2>")?;
+    write_line_chart(
+        &mut out,
+        window,
+        "/s",
+        &[
+            (
+                "requests",
+                report
+                    .points
+                    .iter()
+                    .map(|point| (middle(point), Some(point.per_second())))
+                    .collect(),
+            ),
+            (
+                "errors",
+                report
+                    .points
+                    .iter()
+                    .map(|point| {
+                        let per_second = point.interval.error_count as f64
+                            / point.duration.as_secs_f64().max(f64::EPSILON);
+                        (middle(point), Some(per_second))
+                    })
+                    .collect(),
+            ),
+        ],
+    )?;
+
+    writeln!(out, "<h2>Latency over time</h2>")?;
+    let lines = ["p50", "p90", "p99", "max"]
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let values = report
+                .points
+                .iter()
+                .map(|point| {
+                    let latency = point
+                        .latencies()
+                        .map(|latencies| milliseconds(latencies[index].1));
+                    (middle(point), latency)
+                })
+                .collect();
+            (*name, values)
+        })
+        .collect::<Vec<_>>();
+    write_line_chart(&mut out, window, "ms", &lines)?;
+
+    writeln!(out, "<h2>Latency distribution</h2>")?;
+    write_histogram(&mut out, &requests.latency)?;
+
+    writeln!(out, "<h2>Entries</h2>")?;
+    write!(out, "<table><tr><th>entry</th><th>requests</th>")?;
+    for percent in PERCENTILES {
+        write!(out, "<th>p{}</th>", percent)?;
+    }
+    writeln!(out, "<th>errors</th></tr>")?;
+    for (index, (label, statistics)) in report.statistics.entries.iter().enumerate() {
+        write!(
+            out,
+            "<tr><td>{} {}</td><td class=\"number\">{}</td>",
+            index + 1,
+            escape(label),
+            statistics.request_count
+        )?;
+        for percent in PERCENTILES {
+            write!(
+                out,
+                "<td class=\"number\">{:.3}ms</td>",
+                milliseconds(statistics.latency.percentile(percent))
+            )?;
+        }
+        writeln!(
+            out,
+            "<td class=\"number\">{}</td></tr>",
+            statistics
+                .error_count
+                .0
+                .iter()
+                .map(|(_, n)| n)
+                .sum::<usize>()
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<h2>Responses</h2>")?;
+    write_count(&mut out, "Statuses", &requests.request_status_count)?;
+    write_count(&mut out, "Errors", &requests.error_count)?;
+    write_count(&mut out, "Protocols", &requests.protocol_count)?;
+
+    writeln!(out, "<h2>Configuration</h2>")?;
+    let config = report.config_json();
+    let rows = config
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::Null => "-".to_string(),
+                serde_json::Value::String(value) => value.clone(),
+                serde_json::Value::Array(values) => values
+                    .iter()
+                    .map(|value| value.as_str().unwrap_or_default())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                value => value.to_string(),
+            };
+            (name, value)
+        });
+    write_rows(&mut out, rows)?;
+
+    writeln!(out, "</body></html>")?;
+    Ok(out)
+}
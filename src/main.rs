@@ -1,13 +1,24 @@
 use std::{
-    cmp,
-    collections::BinaryHeap,
-    env, fmt, fs,
+    collections, env, fmt, fs,
     io::{self, Read, Write},
-    ptr, sync, thread, time,
+    mem, path, process, ptr, sync, thread, time,
 };
 
 use anyhow::{Result, anyhow};
-use hurl_core::error::DisplaySourceError;
+use base64::Engine;
+use hurl_core::{error::DisplaySourceError, types::ToSource};
+
+mod assert;
+mod engine;
+mod form;
+mod html;
+mod options;
+mod query;
+mod report;
+mod request_log;
+mod schedule;
+mod series;
+mod threshold;
 
 const USAGE: &str = "
 USAGE:
@@ -21,73 +32,378 @@ OPTIONS:
                                    s = seconds, m = milliseconds
                                    [default: 10s]
 
-    -p, --parallelism <N>          Number of parallel workers
+    -e, --engine <ENGINE>          How the parallel workers are run:
+                                   threads = a thread per worker
+                                   multi = workers shared by --threads
+                                   threads, for thousands of connections
+                                   and HTTP/2 multiplexing
+                                   [default: threads]
+
+        --max-errors <N>           Stop the run early after N errors, e.g.
+                                   connection refused, timeout or a capture
+                                   that could not be evaluated. Without it
+                                   they are counted by kind and the run
+                                   goes on
+
+        --report-html <PATH>       Write a self-contained HTML report once
+                                   the run ended, with charts of the
+                                   throughput and latency over time, the
+                                   latency distribution, the statuses and
+                                   errors, and the run configuration
+
+        --log-requests <PATH>      Write a record of every request sent in
+                                   the measured window, retries included:
+                                   timestamp, worker, entry, method, URL,
+                                   status, error, duration, phase timings,
+                                   bytes in and out. CSV for a .csv path,
+                                   NDJSON otherwise. Records are dropped
+                                   and counted when the disk falls behind
+
+        --interval <DURATION>      Length of the intervals of the time
+                                   series in the reports, with the unit
+                                   suffix of --duration
+                                   [default: 1s]
+
+    -o, --output <FORMAT>[=<PATH>] Write a report once the run ended, to
+                                   stdout without a path, repeatable:
+                                   json = versioned JSON report
+                                   intervals = CSV time series of the
+                                   requests, errors and latency of every
+                                   --interval
+                                   Durations are in seconds, sizes in bytes.
+                                   Only one report, or the bodies of
+                                   --response-body keep=N, goes to stdout
+
+    -p, --parallelism <N>          Number of parallel workers, with --rate
+                                   the size of the worker pool
                                    [default: 1]
 
-    -v, --variable <KEY>=<VALUE>   Pass variables to be expanded
+        --max-streams <N>          Maximum number of HTTP/2 streams
+                                   multiplexed over a connection by the
+                                   multi engine, more open a new connection
+                                   [default: 100, or the server's limit]
+
+        --protocol <PROTOCOL>      HTTP version of every request unless set
+                                   in the [Options] of an entry:
+                                   http1.0, http1.1, http2,
+                                   h2c = HTTP/2 without upgrade over http://
+                                   [default: negotiated by curl]
+
+    -r, --rate <N>/s               Start N iterations per second whatever
+                                   the response times, instead of starting
+                                   the next one when the previous one ends.
+                                   Iterations are late when no worker was
+                                   free on time, dropped when none was free
+                                   before the next one was due
+
+        --response-body <MODE>     What is done with the response bodies:
+                                   discard = only their size is counted
+                                   buffer = buffered like a client would
+                                   keep=N = buffered, the last N bodies of
+                                   every entry are printed after the run
+                                   A body is always buffered when the
+                                   captures or asserts of its entry need it
+                                   [default: discard]
+
+    -s, --significant-digits <N>   Precision of the latency percentiles,
+                                   from 1 to 4, at most 2 for the phases
+                                   and the intervals. Each digit more
+                                   takes several times more memory
+                                   [default: 3]
+
+    -t, --threads <N>              Number of threads of the multi engine
+                                   [default: 1]
+
+        --threshold <EXPR>         Fail the run when a metric of the
+                                   summary crosses a limit, repeatable:
+                                   pN, mean, max = latency with a unit,
+                                   us, ms or s
+                                   rps = requests per second
+                                   rates in % or as a fraction:
+                                   error_rate = transport errors and
+                                   failed captures out of the requests
+                                   http_error_rate = responses neither
+                                   2xx nor 3xx out of the responses
+                                   assert_failure_rate = responses with a
+                                   failed check out of the responses
+                                   compared with <, <=, > or >=,
+                                   e.g. 'p99<250ms' or 'error_rate<1%'
+
+    -v, --variable <KEY>=<VALUE>   Pass variables to be expanded, values are
+                                   typed: null, true/false, numbers, and
+                                   strings for anything else or when quoted
+
+        --variables-file <FILE>    Properties file with one <KEY>=<VALUE>
+                                   variable per line, # starts a comment
+
+Variables are also read from HURL_<KEY> environment variables. They are
+overridden by --variables-file, which is overridden by --variable.
+
+EXIT STATUS:
+    0 once the run ended, 1 on an error or when --max-errors stopped the
+    run, 2 when a --threshold failed
 ";
 
 #[derive(Debug, Clone)]
 struct Variable {
     key: String,
-    value: String,
+    value: query::Value,
 }
 
-#[derive(Debug)]
-struct ByteReader<'a> {
+impl Variable {
+    /// `KEY=VALUE`, only the first `=` separates the key from the value
+    fn parse(definition: &str) -> Result<Self> {
+        let (key, value) = definition
+            .split_once('=')
+            .ok_or_else(|| anyhow!("variable {}: expected <KEY>=<VALUE>", definition))?;
+        Ok(Self {
+            key: key.to_string(),
+            value: Self::typed_value(value),
+        })
+    }
+
+    /// same typing as hurl, a quoted value is always a string
+    fn typed_value(value: &str) -> query::Value {
+        if let Some(v) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            return query::Value::String(v.to_string());
+        }
+        match value {
+            "null" => return query::Value::Null,
+            "true" => return query::Value::Bool(true),
+            "false" => return query::Value::Bool(false),
+            _ => {}
+        }
+        match serde_json::from_str::<serde_json::Number>(value) {
+            Ok(number) => match number.as_i64() {
+                Some(v) => query::Value::Integer(v),
+                None => query::Value::Float(number.as_f64().unwrap_or(f64::NAN)),
+            },
+            Err(_) => query::Value::String(value.to_string()),
+        }
+    }
+
+    /// `HURL_<KEY>` environment variables, `HURL_VARIABLE_<KEY>` as well
+    fn from_env() -> Vec<Self> {
+        env::vars()
+            .filter_map(|(name, value)| {
+                let key = name
+                    .strip_prefix("HURL_VARIABLE_")
+                    .or_else(|| name.strip_prefix("HURL_"))?;
+                Some(Self {
+                    key: key.to_string(),
+                    value: Self::typed_value(&value),
+                })
+            })
+            .collect()
+    }
+
+    /// hurl properties format: a `KEY=VALUE` per line, blank lines and lines
+    /// starting with `#` are skipped
+    fn from_file(filepath: &str) -> Result<Vec<Self>> {
+        let contents = fs::read_to_string(filepath)
+            .map_err(|err| anyhow!("variables file {}: {}", filepath, err))?;
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Self::parse)
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ByteReader {
     index: usize,
-    slice: &'a [u8],
+    slice: sync::Arc<[u8]>,
 }
 
-impl<'a> ByteReader<'a> {
-    fn new(slice: &'a [u8]) -> Self {
+impl ByteReader {
+    fn new(slice: sync::Arc<[u8]>) -> Self {
         Self { slice, index: 0 }
     }
 }
 
-impl<'a> io::Read for ByteReader<'a> {
+impl io::Read for ByteReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if self.index >= self.slice.len() {
             return Ok(0);
         }
 
-        let copied = self.slice.len().min(buf.len());
-        unsafe { ptr::copy_nonoverlapping(self.slice.as_ptr(), buf.as_mut_ptr(), copied) };
-        self.index += buf.len();
+        let copied = (self.slice.len() - self.index).min(buf.len());
+        unsafe {
+            ptr::copy_nonoverlapping(
+                self.slice.as_ptr().add(self.index),
+                buf.as_mut_ptr(),
+                copied,
+            )
+        };
+        self.index += copied;
         Ok(copied)
     }
 }
 
+/// transfer callbacks of a client, the response headers and body are only
+/// kept when `collect` is set, otherwise the body is only counted
+#[derive(Debug, Default)]
+struct Collector {
+    collect: bool,
+    request_body: ByteReader,
+    version: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    body_size: u64,
+}
+
+impl Collector {
+    fn new(collect: bool, request_body: Option<sync::Arc<[u8]>>) -> Self {
+        Self {
+            collect,
+            request_body: ByteReader::new(request_body.unwrap_or_default()),
+            ..Default::default()
+        }
+    }
+}
+
+impl curl::easy::Handler for Collector {
+    fn write(&mut self, data: &[u8]) -> Result<usize, curl::easy::WriteError> {
+        if self.collect {
+            self.body.extend_from_slice(data);
+        }
+        self.body_size += data.len() as u64;
+        Ok(data.len())
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> Result<usize, curl::easy::ReadError> {
+        Ok(self.request_body.read(data).unwrap())
+    }
+
+    fn header(&mut self, data: &[u8]) -> bool {
+        if !self.collect {
+            return true;
+        }
+        let line = String::from_utf8_lossy(data);
+        let line = line.trim_end();
+        if line.starts_with("HTTP/") {
+            // a new status line starts another header block, e.g. after a redirect
+            self.version = line.split(' ').next().unwrap_or_default().to_string();
+            self.headers.clear();
+        } else if let Some((key, value)) = line.split_once(':') {
+            self.headers
+                .push((key.trim().to_string(), value.trim().to_string()));
+        }
+        true
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Endpoint {
     url: String,
     headers: Vec<(String, String)>,
     method: String,
-    body: Option<Vec<u8>>,
+    /// shared with the scenario for a `file,` body
+    body: Option<sync::Arc<[u8]>>,
 }
 
 #[derive(Debug, Clone)]
 struct TemplateResolver {
     variables: Vec<Variable>,
+    /// directory that relative `file,` paths are read from
+    file_root: path::PathBuf,
+    /// contents of the `file,` paths read when the scenario is loaded, see
+    /// `load_files`, shared by the clones. A path that depends on a capture
+    /// is read on every use instead, so that this stays bounded
+    files: sync::Arc<collections::HashMap<path::PathBuf, sync::Arc<[u8]>>>,
+}
+
+/// RFC 3339 UTC date with microseconds, as rendered by hurl `newDate`
+fn new_date(system_time: time::SystemTime) -> String {
+    let since_epoch = system_time
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // civil date from days since epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_micros()
+    )
 }
 
 impl TemplateResolver {
-    fn new(variables: Vec<Variable>) -> Self {
-        Self { variables }
+    /// a later variable overrides an earlier one with the same key
+    fn new(variables: Vec<Variable>, file_root: path::PathBuf) -> Self {
+        let mut template_resolver = Self {
+            variables: Vec::new(),
+            file_root,
+            files: Default::default(),
+        };
+        for variable in variables {
+            template_resolver.set_variable(variable.key, variable.value);
+        }
+        template_resolver
     }
 
-    fn resolve_placeholder(&self, placeholder: &hurl_core::ast::Placeholder) -> Result<String> {
+    fn get_variable(&self, key: &str) -> Option<&query::Value> {
+        self.variables
+            .iter()
+            .find(|v| v.key == key)
+            .map(|v| &v.value)
+    }
+
+    fn set_variable(&mut self, key: String, value: query::Value) {
+        match self.variables.iter_mut().find(|v| v.key == key) {
+            Some(variable) => variable.value = value,
+            None => self.variables.push(Variable { key, value }),
+        }
+    }
+
+    /// functions are evaluated on every call, so that every request gets its
+    /// own value. The hurl 7.1 grammar has no filters on placeholders
+    fn resolve_placeholder_value(
+        &self,
+        placeholder: &hurl_core::ast::Placeholder,
+    ) -> Result<query::Value> {
         match &placeholder.expr.kind {
             hurl_core::ast::ExprKind::Variable(variable) => self
-                .variables
-                .iter()
-                .find(|v| &v.key == &variable.name)
-                .map(|v| v.value.clone())
+                .get_variable(&variable.name)
+                .cloned()
                 .ok_or_else(|| anyhow!("variable: {} not found", &variable.name)),
-            _ => Err(anyhow!("this expression type is not supported")),
+            hurl_core::ast::ExprKind::Function(hurl_core::ast::Function::NewUuid) => {
+                Ok(query::Value::String(uuid::Uuid::new_v4().to_string()))
+            }
+            hurl_core::ast::ExprKind::Function(hurl_core::ast::Function::NewDate) => {
+                Ok(query::Value::String(new_date(time::SystemTime::now())))
+            }
         }
     }
 
+    fn resolve_placeholder(&self, placeholder: &hurl_core::ast::Placeholder) -> Result<String> {
+        Ok(self.resolve_placeholder_value(placeholder)?.to_string())
+    }
+
     fn resolve(&self, template: &hurl_core::ast::Template) -> Result<String> {
         let mut string = String::new();
 
@@ -105,20 +421,151 @@ impl TemplateResolver {
 
         Ok(string)
     }
+
+    /// placeholders are kept as they are written in the hurl file
+    fn resolve_raw(template: &hurl_core::ast::Template) -> String {
+        template
+            .elements
+            .iter()
+            .map(|element| match element {
+                hurl_core::ast::TemplateElement::String { value, .. } => value.clone(),
+                hurl_core::ast::TemplateElement::Placeholder(placeholder) => {
+                    placeholder.to_source().to_string()
+                }
+            })
+            .collect()
+    }
+
+    fn resolve_file(&self, filename: &hurl_core::ast::Template) -> Result<sync::Arc<[u8]>> {
+        let path = self.file_root.join(self.resolve(filename)?);
+        match self.files.get(&path) {
+            Some(contents) => Ok(contents.clone()),
+            None => read_file(&path),
+        }
+    }
+
+    /// reads the `file,` bodies and multipart files of the entries once,
+    /// except the ones whose path depends on a capture
+    fn load_files(&mut self, entries: &[hurl_core::ast::Entry]) -> Result<()> {
+        let mut files = collections::HashMap::new();
+        for entry in entries {
+            let bodies = [
+                entry.request.body.as_ref(),
+                entry.response.as_ref().and_then(|v| v.body.as_ref()),
+            ];
+            let filenames = bodies
+                .into_iter()
+                .flatten()
+                .filter_map(|body| match &body.value {
+                    hurl_core::ast::Bytes::File(file) => Some(&file.filename),
+                    _ => None,
+                })
+                .chain(entry.request.multipart_form_data().iter().filter_map(
+                    |param| match param {
+                        hurl_core::ast::MultipartParam::FilenameParam(filename_param) => {
+                            Some(&filename_param.value.filename)
+                        }
+                        hurl_core::ast::MultipartParam::Param(_) => None,
+                    },
+                ));
+            for filename in filenames {
+                let Ok(filename) = self.resolve(filename) else {
+                    continue;
+                };
+                let path = self.file_root.join(filename);
+                if let collections::hash_map::Entry::Vacant(file) = files.entry(path) {
+                    let contents = read_file(file.key())?;
+                    file.insert(contents);
+                }
+            }
+        }
+        self.files = sync::Arc::new(files);
+        Ok(())
+    }
+
+    fn resolve_multiline(&self, multiline: &hurl_core::ast::MultilineString) -> Result<String> {
+        let no_variable = multiline
+            .attributes
+            .contains(&hurl_core::ast::MultilineStringAttribute::NoVariable);
+        let resolve = |template| {
+            if no_variable {
+                Ok(Self::resolve_raw(template))
+            } else {
+                self.resolve(template)
+            }
+        };
+
+        match &multiline.kind {
+            hurl_core::ast::MultilineStringKind::Text(template)
+            | hurl_core::ast::MultilineStringKind::Json(template)
+            | hurl_core::ast::MultilineStringKind::Xml(template) => resolve(template),
+            hurl_core::ast::MultilineStringKind::GraphQl(graphql) => {
+                let mut string = format!(
+                    "{{\"query\":{}",
+                    JsonResolver::quote(&resolve(&graphql.value)?)
+                );
+                if let Some(variables) = &graphql.variables {
+                    string.push_str(",\"variables\":");
+                    string.push_str(&JsonResolver::new(self).resolve(&variables.value)?);
+                }
+                string.push('}');
+                Ok(string)
+            }
+        }
+    }
+
+    /// a `file,` body is shared rather than copied
+    fn resolve_bytes(&self, bytes: &hurl_core::ast::Bytes) -> Result<sync::Arc<[u8]>> {
+        Ok(match bytes {
+            hurl_core::ast::Bytes::Json(json_value) => {
+                JsonResolver::new(self).resolve(json_value)?.into_bytes()
+            }
+            hurl_core::ast::Bytes::Xml(xml) => xml.clone().into_bytes(),
+            hurl_core::ast::Bytes::MultilineString(multiline) => {
+                self.resolve_multiline(multiline)?.into_bytes()
+            }
+            hurl_core::ast::Bytes::OnelineString(template) => self.resolve(template)?.into_bytes(),
+            hurl_core::ast::Bytes::Base64(base64) => base64.value.clone(),
+            hurl_core::ast::Bytes::Hex(hex) => hex.value.clone(),
+            hurl_core::ast::Bytes::File(file) => return self.resolve_file(&file.filename),
+        }
+        .into())
+    }
+}
+
+fn read_file(path: &path::Path) -> Result<sync::Arc<[u8]>> {
+    Ok(fs::read(path)
+        .map_err(|err| anyhow!("file {}: {}", path.display(), err))?
+        .into())
+}
+
+/// content type hurl sends when the entry does not set one
+fn implicit_content_type(bytes: &hurl_core::ast::Bytes) -> Option<&'static str> {
+    match bytes {
+        hurl_core::ast::Bytes::Json(_) => Some("application/json"),
+        hurl_core::ast::Bytes::Xml(_) => Some("application/xml"),
+        hurl_core::ast::Bytes::MultilineString(multiline) => match multiline.kind {
+            hurl_core::ast::MultilineStringKind::Json(_)
+            | hurl_core::ast::MultilineStringKind::GraphQl(_) => Some("application/json"),
+            hurl_core::ast::MultilineStringKind::Xml(_) => Some("application/xml"),
+            hurl_core::ast::MultilineStringKind::Text(_) => None,
+        },
+        _ => None,
+    }
 }
 
 #[derive(Debug)]
-struct JsonResolver {
-    template_resolver: TemplateResolver,
+struct JsonResolver<'a> {
+    template_resolver: &'a TemplateResolver,
 }
 
-impl JsonResolver {
-    fn new(template_resolver: TemplateResolver) -> Self {
+impl<'a> JsonResolver<'a> {
+    fn new(template_resolver: &'a TemplateResolver) -> Self {
         Self { template_resolver }
     }
 
     fn quote(string: &str) -> String {
-        format!("\"{}\"", string.replace("\"", "\\\""))
+        serde_json::Value::String(string.to_string()).to_string()
     }
 
     fn resolve(&self, json_value: &hurl_core::ast::JsonValue) -> Result<String> {
@@ -131,9 +578,12 @@ impl JsonResolver {
             hurl_core::ast::JsonValue::String(template) => {
                 Self::quote(&self.template_resolver.resolve(template)?)
             }
-            hurl_core::ast::JsonValue::Placeholder(placeholder) => {
-                self.template_resolver.resolve_placeholder(placeholder)?
-            }
+            // typed, a string variable is quoted
+            hurl_core::ast::JsonValue::Placeholder(placeholder) => self
+                .template_resolver
+                .resolve_placeholder_value(placeholder)?
+                .to_json()
+                .to_string(),
             hurl_core::ast::JsonValue::List {
                 space0: _space0,
                 elements,
@@ -173,12 +623,27 @@ impl JsonResolver {
 impl Endpoint {
     fn new(
         template_resolver: &TemplateResolver,
-        json_resolver: &JsonResolver,
         entry: &hurl_core::ast::Entry,
+        entry_options: &options::EntryOptions,
     ) -> Result<Self> {
-        let url = template_resolver.resolve(&entry.request.url)?;
-        let headers = entry
-            .request
+        let request = &entry.request;
+        let mut url = template_resolver.resolve(&request.url)?;
+        let querystring_params = request.querystring_params();
+        if !querystring_params.is_empty() {
+            let query = form::encode_params(template_resolver, querystring_params)?;
+            let (base, fragment) = match url.split_once('#') {
+                Some((base, fragment)) => (base.to_string(), Some(fragment.to_string())),
+                None => (url, None),
+            };
+            let separator = if base.contains('?') { '&' } else { '?' };
+            url = format!("{}{}{}", base, separator, query);
+            if let Some(fragment) = fragment {
+                url.push('#');
+                url.push_str(&fragment);
+            }
+        }
+
+        let mut headers: Vec<(String, String)> = request
             .headers
             .iter()
             .map(|key_value| {
@@ -188,60 +653,148 @@ impl Endpoint {
                 ))
             })
             .collect::<Result<_>>()?;
-
-        let body = entry
-            .request
-            .body
-            .as_ref()
-            .map(|body| match &body.value {
-                hurl_core::ast::Bytes::Json(json_value) => {
-                    let result = json_resolver.resolve(json_value)?;
-                    Ok(result.into_bytes())
-                }
-                _ => Err(anyhow!("this body is not yet supported")),
+        headers.extend(entry_options.headers.iter().cloned());
+        if let Some(basic_auth) = request.basic_auth() {
+            let credentials = format!(
+                "{}:{}",
+                template_resolver.resolve(&basic_auth.key)?,
+                template_resolver.resolve(&basic_auth.value)?
+            );
+            headers.push((
+                "Authorization".to_string(),
+                format!(
+                    "Basic {}",
+                    base64::engine::general_purpose::STANDARD.encode(credentials)
+                ),
+            ));
+        }
+        let cookies = request
+            .cookies()
+            .iter()
+            .map(|cookie| {
+                Ok(format!(
+                    "{}={}",
+                    template_resolver.resolve(&cookie.name)?,
+                    template_resolver.resolve(&cookie.value)?
+                ))
             })
-            .transpose()?;
+            .collect::<Result<Vec<_>>>()?;
+        if !cookies.is_empty() {
+            headers.push(("Cookie".to_string(), cookies.join("; ")));
+        }
+
+        let form_params = request.form_params();
+        let multipart_form_data = request.multipart_form_data();
+        if [
+            request.body.is_some(),
+            !form_params.is_empty(),
+            !multipart_form_data.is_empty(),
+        ]
+        .iter()
+        .filter(|v| **v)
+        .count()
+            > 1
+        {
+            return Err(anyhow!(
+                "only one of body, [FormParams] and [MultipartFormData] can be set"
+            ));
+        }
+
+        let (body, content_type) = if !form_params.is_empty() {
+            (
+                Some(
+                    form::encode_params(template_resolver, form_params)?
+                        .into_bytes()
+                        .into(),
+                ),
+                Some("application/x-www-form-urlencoded".to_string()),
+            )
+        } else if !multipart_form_data.is_empty() {
+            let boundary = form::multipart_boundary();
+            (
+                Some(
+                    form::encode_multipart(template_resolver, multipart_form_data, &boundary)?
+                        .into(),
+                ),
+                Some(format!("multipart/form-data; boundary={}", boundary)),
+            )
+        } else if let Some(body) = &request.body {
+            (
+                Some(template_resolver.resolve_bytes(&body.value)?),
+                implicit_content_type(&body.value).map(|v| v.to_string()),
+            )
+        } else {
+            (None, None)
+        };
+
+        if body.is_some()
+            && !headers
+                .iter()
+                .any(|(key, _)| key.eq_ignore_ascii_case("content-type"))
+        {
+            // an empty value stops curl from sending its own form content type
+            headers.push(("Content-Type".to_string(), content_type.unwrap_or_default()));
+        }
 
         Ok(Self {
             url,
             headers,
             body,
-            method: entry.request.method.to_string(),
+            method: request.method.to_string(),
         })
     }
 
     fn create_header_list(&self) -> Result<curl::easy::List> {
         let mut list = curl::easy::List::new();
         for (key, value) in &self.headers {
-            list.append(&format!("{}: {}", key, value))?;
+            if value.is_empty() {
+                list.append(&format!("{}:", key))?;
+            } else {
+                list.append(&format!("{}: {}", key, value))?;
+            }
         }
         Ok(list)
     }
 
-    fn send_request(&self, client: &mut curl::easy::Easy) -> Result<u32> {
+    /// sets up the client for the request, ready to be performed. When
+    /// `collect` is false the response headers and body are not kept
+    fn prepare(
+        self,
+        client: &mut curl::easy::Easy2<Collector>,
+        entry_options: &options::EntryOptions,
+        collect: bool,
+    ) -> Result<()> {
         client.reset();
+        entry_options.apply(client)?;
         client.url(&self.url)?;
         client.http_headers(self.create_header_list()?)?;
-        match self.method.as_str() {
-            "GET" => client.get(true),
-            "POST" => client.post(true),
-            "PUT" => client.put(true),
-            _ => todo!("unknown method handle"),
-        }?;
-
-        {
-            let mut transfer = client.transfer();
-            if let Some(body) = &self.body {
-                let mut reader = ByteReader::new(body);
-                transfer.read_function(move |data| Ok(reader.read(data).unwrap()))?;
-                transfer.write_function(|data| {
-                    // noop
-                    Ok(data.len())
-                })?;
+        match (self.method.as_str(), &self.body) {
+            // without a body curl would wait for one
+            ("HEAD", _) => client.nobody(true)?,
+            ("GET", None) => client.get(true)?,
+            // any method can carry a body, curl sends it as a POST whose verb
+            // is overridden below. A known size sends Content-Length instead
+            // of a chunked body
+            (_, Some(body)) => {
+                client.post(true)?;
+                client.post_field_size(body.len() as u64)?;
             }
-            transfer.perform()?;
+            ("POST", None) => {
+                client.post(true)?;
+                client.post_field_size(0)?;
+            }
+            _ => {}
+        }
+        // with a body every verb but POST is overridden, a GET included
+        let custom = match &self.body {
+            Some(_) => self.method != "POST",
+            None => !matches!(self.method.as_str(), "GET" | "HEAD" | "POST"),
+        };
+        if custom {
+            client.custom_request(&self.method)?;
         }
-        Ok(client.response_code()?)
+        *client.get_mut() = Collector::new(collect, self.body);
+        Ok(())
     }
 }
 
@@ -262,9 +815,87 @@ impl fmt::Display for Endpoint {
     }
 }
 
+/// not exported by curl-sys
+const CURLINFO_HTTP_VERSION: curl_sys::CURLINFO = curl_sys::CURLINFO_LONG + 46;
+
+/// HTTP version of the last response of a client, a fallback from HTTP/2 to
+/// HTTP/1.1 shows here
+fn negotiated_protocol<H>(client: &curl::easy::Easy2<H>) -> Result<&'static str> {
+    let mut version: std::os::raw::c_long = 0;
+    let code =
+        unsafe { curl_sys::curl_easy_getinfo(client.raw(), CURLINFO_HTTP_VERSION, &mut version) };
+    if code != curl_sys::CURLE_OK {
+        return Err(curl::Error::new(code).into());
+    }
+    Ok(match version as std::os::raw::c_int {
+        curl_sys::CURL_HTTP_VERSION_1_0 => "HTTP/1.0",
+        curl_sys::CURL_HTTP_VERSION_1_1 => "HTTP/1.1",
+        curl_sys::CURL_HTTP_VERSION_2_0 => "HTTP/2",
+        curl_sys::CURL_HTTP_VERSION_3 => "HTTP/3",
+        _ => "unknown",
+    })
+}
+
+#[derive(Debug)]
+struct HttpResponse {
+    status: u32,
+    version: String,
+    /// as negotiated by curl, whatever the status line says
+    protocol: &'static str,
+    phases: Phases,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    /// even when the body is not kept
+    body_size: u64,
+    url: String,
+    duration: time::Duration,
+}
+
+impl HttpResponse {
+    /// response of a client once performed, `duration` since it was sent
+    fn new(client: &mut curl::easy::Easy2<Collector>, duration: time::Duration) -> Result<Self> {
+        let status = client.response_code()?;
+        let protocol = negotiated_protocol(client)?;
+        let phases = Phases::new(client)?;
+        let url = client.effective_url()?.unwrap_or_default().to_string();
+        let collector = client.get_mut();
+        Ok(Self {
+            status,
+            version: mem::take(&mut collector.version),
+            protocol,
+            phases,
+            headers: mem::take(&mut collector.headers),
+            body: mem::take(&mut collector.body),
+            body_size: collector.body_size,
+            url,
+            duration,
+        })
+    }
+
+    fn headers(&self, name: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+}
+
 struct CmdArgs {
     parrallelism: usize,
+    engine: engine::Engine,
+    threads: usize,
+    max_streams: Option<usize>,
+    max_errors: Option<usize>,
+    protocol: Option<curl::easy::HttpVersion>,
+    response_body: engine::ResponseBody,
+    outputs: Vec<report::Output>,
+    interval: time::Duration,
+    thresholds: Vec<threshold::Threshold>,
+    log_requests: Option<String>,
     duration: time::Duration,
+    rate: Option<f64>,
+    significant_digits: u8,
     filepath: String,
     variables: Vec<Variable>,
 }
@@ -273,64 +904,165 @@ impl CmdArgs {
     fn new() -> Result<Self> {
         let mut duration = None;
         let mut parallelism = None;
+        let mut engine = None;
+        let mut threads = None;
+        let mut max_streams = None;
+        let mut max_errors = None;
+        let mut protocol = None;
+        let mut response_body = None;
+        let mut outputs = Vec::new();
+        let mut interval = None;
+        let mut thresholds = Vec::new();
+        let mut log_requests = None;
+        let mut rate = None;
+        let mut significant_digits = None;
         let mut filepath = None;
         let mut variables = Vec::new();
+        let mut variables_files = Vec::new();
 
         let missing_argument = || anyhow!("missing argument");
         let mut args = env::args().skip(1);
-        loop {
-            let Some(arg) = args.next() else {
-                break;
-            };
+        while let Some(arg) = args.next() {
             match arg.as_str() {
                 "-d" | "--duration" => {
-                    let duration_string = args.next().ok_or_else(missing_argument)?;
-                    let duration_int: usize =
-                        duration_string[..duration_string.len() - 1].parse()?;
-                    duration = Some(
-                        match duration_string
-                            .as_bytes()
-                            .last()
-                            .ok_or_else(missing_argument)?
-                            .to_ascii_lowercase()
-                        {
-                            b's' => time::Duration::from_secs(duration_int as u64),
-                            b'm' => time::Duration::from_millis(duration_int as u64),
-                            v => {
-                                return Err(anyhow!(
-                                    "unknown time modifier {}, expected s/m",
-                                    v as char
-                                ));
-                            }
-                        },
-                    );
+                    duration = Some(parse_duration(&args.next().ok_or_else(missing_argument)?)?);
+                }
+                "-e" | "--engine" => {
+                    engine = Some(engine::Engine::parse(
+                        &args.next().ok_or_else(missing_argument)?,
+                    )?);
+                }
+                "--max-errors" => {
+                    let count = args.next().ok_or_else(missing_argument)?.parse()?;
+                    if count == 0 {
+                        return Err(anyhow!("max errors {}: expected at least 1", count));
+                    }
+                    max_errors = Some(count);
+                }
+                "--max-streams" => {
+                    let count = args.next().ok_or_else(missing_argument)?.parse()?;
+                    if count == 0 {
+                        return Err(anyhow!("max streams {}: expected at least 1", count));
+                    }
+                    max_streams = Some(count);
+                }
+                "--report-html" => {
+                    outputs.push(report::Output::Html {
+                        path: args.next().ok_or_else(missing_argument)?,
+                    });
+                }
+                "--log-requests" => {
+                    log_requests = Some(args.next().ok_or_else(missing_argument)?);
+                }
+                "--interval" => {
+                    let length = parse_duration(&args.next().ok_or_else(missing_argument)?)?;
+                    if length.is_zero() {
+                        return Err(anyhow!("interval must be at least 1m"));
+                    }
+                    interval = Some(length);
+                }
+                "-o" | "--output" => {
+                    outputs.push(report::Output::parse(
+                        &args.next().ok_or_else(missing_argument)?,
+                    )?);
                 }
                 "-p" | "--parallelism" => {
                     parallelism = Some(args.next().ok_or_else(missing_argument)?.parse()?);
                 }
+                "--protocol" => {
+                    protocol = Some(options::parse_protocol(
+                        &args.next().ok_or_else(missing_argument)?,
+                    )?);
+                }
+                "-r" | "--rate" => {
+                    rate = Some(schedule::parse_rate(
+                        &args.next().ok_or_else(missing_argument)?,
+                    )?);
+                }
+                "--response-body" => {
+                    response_body = Some(engine::ResponseBody::parse(
+                        &args.next().ok_or_else(missing_argument)?,
+                    )?);
+                }
+                "-s" | "--significant-digits" => {
+                    let digits = args.next().ok_or_else(missing_argument)?.parse()?;
+                    if !(1..=4).contains(&digits) {
+                        return Err(anyhow!("significant digits {}: expected 1 to 4", digits));
+                    }
+                    significant_digits = Some(digits);
+                }
+                "-t" | "--threads" => {
+                    let count = args.next().ok_or_else(missing_argument)?.parse()?;
+                    if count == 0 {
+                        return Err(anyhow!("threads {}: expected at least 1", count));
+                    }
+                    threads = Some(count);
+                }
+                "--threshold" => {
+                    thresholds.push(threshold::Threshold::parse(
+                        &args.next().ok_or_else(missing_argument)?,
+                    )?);
+                }
                 "-v" | "--variable" => {
-                    let definition = args.next().ok_or_else(missing_argument)?;
-                    let mut by_equals = definition.split("=");
-                    let key = by_equals.next().ok_or_else(missing_argument)?;
-                    let value = by_equals.next().ok_or_else(missing_argument)?;
-                    variables.push(Variable {
-                        key: key.to_string(),
-                        value: value.to_string(),
-                    });
+                    variables.push(Variable::parse(&args.next().ok_or_else(missing_argument)?)?);
+                }
+                "--variables-file" => {
+                    variables_files.push(args.next().ok_or_else(missing_argument)?);
                 }
                 v => filepath = Some(v.to_string()),
             };
         }
 
+        // machine readable output has stdout to itself
+        let stdout_users = outputs.iter().filter(|output| output.is_stdout()).count()
+            + usize::from(matches!(response_body, Some(engine::ResponseBody::Keep(_))));
+        if stdout_users > 1 {
+            return Err(anyhow!(
+                "only one of --output without a path and --response-body keep=N can write to stdout"
+            ));
+        }
+
         Ok(Self {
             duration: duration.unwrap_or(time::Duration::from_secs(10)),
             parrallelism: parallelism.unwrap_or(1),
+            engine: engine.unwrap_or(engine::Engine::Threads),
+            threads: threads.unwrap_or(1),
+            max_streams,
+            max_errors,
+            protocol,
+            response_body: response_body.unwrap_or(engine::ResponseBody::Discard),
+            outputs,
+            interval: interval.unwrap_or(time::Duration::from_secs(1)),
+            thresholds,
+            log_requests,
+            rate,
+            significant_digits: significant_digits.unwrap_or(3),
             filepath: filepath.ok_or_else(missing_argument)?,
-            variables,
+            variables: {
+                let mut all_variables = Variable::from_env();
+                for variables_file in &variables_files {
+                    all_variables.extend(Variable::from_file(variables_file)?);
+                }
+                all_variables.extend(variables);
+                all_variables
+            },
         })
     }
 }
 
+/// `N` followed by `s` for seconds or `m` for milliseconds
+fn parse_duration(duration: &str) -> Result<time::Duration> {
+    let Some(unit) = duration.as_bytes().last() else {
+        return Err(anyhow!("missing argument"));
+    };
+    let count: u64 = duration[..duration.len() - 1].parse()?;
+    match unit.to_ascii_lowercase() {
+        b's' => Ok(time::Duration::from_secs(count)),
+        b'm' => Ok(time::Duration::from_millis(count)),
+        v => Err(anyhow!("unknown time modifier {}, expected s/m", v as char)),
+    }
+}
+
 impl fmt::Display for CmdArgs {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -339,91 +1071,195 @@ impl fmt::Display for CmdArgs {
             &self.filepath,
             self.duration.as_secs_f32(),
             self.parrallelism
-        )
+        )?;
+        if self.engine == engine::Engine::Multi {
+            write!(f, ", engine: {}, threads: {}", self.engine, self.threads)?;
+        }
+        if let Some(max_errors) = self.max_errors {
+            write!(f, ", max_errors: {}", max_errors)?;
+        }
+        if let Some(max_streams) = self.max_streams {
+            write!(f, ", max_streams: {}", max_streams)?;
+        }
+        if let Some(protocol) = self.protocol {
+            write!(f, ", protocol: {}", options::protocol_name(protocol))?;
+        }
+        if self.response_body != engine::ResponseBody::Discard {
+            write!(f, ", response_body: {}", self.response_body)?;
+        }
+        if let Some(rate) = self.rate {
+            write!(f, ", rate: {}/s", rate)?;
+        }
+        if self.interval != time::Duration::from_secs(1) {
+            write!(f, ", interval_s: {:.3}", self.interval.as_secs_f64())?;
+        }
+        for threshold in &self.thresholds {
+            write!(f, ", threshold: {}", threshold)?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
-struct PLatencyBucket {
-    count: usize,
-    max_ms: f32,
-}
+/// microseconds, a longer duration is tracked as the highest value
+const LATENCY_MAX_US: u64 = 10 * 60 * 1_000_000;
 
-#[derive(Debug)]
-struct PLatency {
-    buckets: Vec<PLatencyBucket>,
-    total: usize,
-}
+/// precision of the histograms there are many of, see `Latency::coarse`
+const COARSE_SIGNIFICANT_DIGITS: u8 = 2;
 
-impl PLatency {
-    fn new(bucket_count: usize, granularity: f32, exp: f32) -> Self {
-        Self {
-            buckets: (1..=bucket_count)
-                .map(|i| PLatencyBucket {
-                    count: 0,
-                    max_ms: (i as f32 * granularity) * exp.powf(i as f32),
-                })
-                .collect(),
-            total: 0,
-        }
+/// high dynamic range histogram of durations, from a microsecond to 10
+/// minutes with `significant_digits` precision
+#[derive(Debug, Clone)]
+struct Latency(hdrhistogram::Histogram<u64>);
+
+impl Latency {
+    fn new(significant_digits: u8) -> Self {
+        Self(
+            hdrhistogram::Histogram::new_with_bounds(1, LATENCY_MAX_US, significant_digits)
+                .expect("valid histogram bounds"),
+        )
+    }
+
+    /// for the phases and the intervals, a histogram takes several times less
+    /// memory with a digit less
+    fn coarse(significant_digits: u8) -> Self {
+        Self::new(significant_digits.min(COARSE_SIGNIFICANT_DIGITS))
     }
 
     fn track(&mut self, duration: time::Duration) {
-        self.total += 1;
+        self.0.saturating_record(duration.as_micros() as u64);
+    }
 
-        let ms = duration.as_secs_f32() * 1000.0;
-        for bucket in self.buckets.iter_mut().rev() {
-            if bucket.max_ms <= ms {
-                bucket.count += 1;
-                return;
-            }
+    /// also tracks the durations of the requests that would have been sent
+    /// every `expected_interval` while this one was pending
+    fn track_with_expected_interval(
+        &mut self,
+        duration: time::Duration,
+        expected_interval: time::Duration,
+    ) {
+        let value = (duration.as_micros() as u64).min(LATENCY_MAX_US);
+        let interval = expected_interval.as_micros() as u64;
+        if self.0.record_correct(value, interval).is_err() {
+            self.0.saturating_record(value);
         }
+    }
 
-        if let Some(bucket) = self.buckets.first_mut() {
-            bucket.count += 1;
-        }
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 
-    fn p_ms(&self, percent: f32) -> f32 {
-        let mut count = 0;
-        for (i, bucket) in self.buckets.iter().enumerate() {
-            count += bucket.count;
-            if ((count as f32 / self.total as f32) * 100.0) >= percent {
-                if i == 0 {
-                    return bucket.max_ms;
-                }
+    fn mean(&self) -> time::Duration {
+        time::Duration::from_secs_f64(self.0.mean() / 1_000_000.0)
+    }
 
-                let last_bucket = self.buckets.get(i - 1).unwrap();
-                return (last_bucket.max_ms + bucket.max_ms) / 2.0;
-            }
+    fn stddev(&self) -> time::Duration {
+        time::Duration::from_secs_f64(self.0.stdev() / 1_000_000.0)
+    }
+
+    fn percentile(&self, percent: f64) -> time::Duration {
+        time::Duration::from_micros(self.0.value_at_quantile(percent / 100.0))
+    }
+
+    fn reset(&mut self) {
+        // clearing walks every bucket
+        if !self.is_empty() {
+            self.0.reset();
         }
+    }
 
-        0.0
+    fn merge(&mut self, other: &Self) {
+        // same bounds, adding can not fail
+        self.0
+            .add(&other.0)
+            .expect("histograms with the same bounds");
     }
 }
 
-#[derive(Debug)]
-struct RequestStatusCount(Vec<(usize, usize)>);
+/// curl's timings of a request, each phase from the end of the previous one
+#[derive(Debug, Clone, Copy, Default)]
+struct Phases {
+    dns: time::Duration,
+    connect: time::Duration,
+    /// zero without TLS
+    tls: time::Duration,
+    /// from the end of the handshakes to the first byte of the response
+    ttfb: time::Duration,
+    transfer: time::Duration,
+    total: time::Duration,
+}
 
-impl RequestStatusCount {
+impl Phases {
+    const NAMES: [&str; 6] = ["dns", "connect", "tls", "ttfb", "transfer", "total"];
+
+    /// timings of the last transfer of a client, a reused connection has no
+    /// dns, connect nor tls time
+    fn new<H>(client: &mut curl::easy::Easy2<H>) -> Result<Self> {
+        let namelookup = client.namelookup_time()?;
+        let connect = client.connect_time()?.max(namelookup);
+        // zero without TLS
+        let appconnect = client.appconnect_time()?;
+        let handshakes = connect.max(appconnect);
+        let starttransfer = client.starttransfer_time()?.max(handshakes);
+        let total = client.total_time()?.max(starttransfer);
+        Ok(Self {
+            dns: namelookup,
+            connect: connect - namelookup,
+            tls: handshakes - connect,
+            ttfb: starttransfer - handshakes,
+            transfer: total - starttransfer,
+            total,
+        })
+    }
+
+    /// in the order of `NAMES`
+    fn durations(&self) -> [time::Duration; 6] {
+        [
+            self.dns,
+            self.connect,
+            self.tls,
+            self.ttfb,
+            self.transfer,
+            self.total,
+        ]
+    }
+}
+
+/// number of responses by status, or by protocol
+#[derive(Debug, Clone)]
+struct ResponseCount<K>(Vec<(K, usize)>);
+
+impl<K: Copy + Ord> ResponseCount<K> {
     fn new() -> Self {
         Self(Vec::new())
     }
 
-    fn track(&mut self, status: usize) {
+    fn track(&mut self, key: K) {
+        self.track_n(key, 1);
+    }
+
+    fn track_n(&mut self, key: K, n: usize) {
         for count in self.0.iter_mut() {
-            if count.0 == status {
-                count.1 += 1;
+            if count.0 == key {
+                count.1 += n;
                 return;
             }
         }
 
-        self.0.push((status, 1));
-        self.0.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        self.0.push((key, n));
+        self.0.sort_unstable_by_key(|v| v.0);
+    }
+
+    fn reset(&mut self) {
+        self.0.clear();
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (key, n) in &other.0 {
+            self.track_n(*key, *n);
+        }
     }
 }
 
-impl fmt::Display for RequestStatusCount {
+impl<K: fmt::Display> fmt::Display for ResponseCount<K> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut string = String::new();
         for v in &self.0 {
@@ -433,41 +1269,252 @@ impl fmt::Display for RequestStatusCount {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+struct AssertCount {
+    passed: usize,
+    failed: usize,
+    /// label of every check of the entry with the number of times it failed
+    failures: Vec<(String, usize)>,
+}
+
+impl AssertCount {
+    fn new(labels: Vec<String>) -> Self {
+        Self {
+            passed: 0,
+            failed: 0,
+            failures: labels.into_iter().map(|label| (label, 0)).collect(),
+        }
+    }
+
+    fn track(&mut self, failed_asserts: &[usize]) {
+        if failed_asserts.is_empty() {
+            self.passed += 1;
+            return;
+        }
+
+        self.failed += 1;
+        for index in failed_asserts {
+            self.failures[*index].1 += 1;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.passed = 0;
+        self.failed = 0;
+        for failure in &mut self.failures {
+            failure.1 = 0;
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+        for (failure, other_failure) in self.failures.iter_mut().zip(&other.failures) {
+            failure.1 += other_failure.1;
+        }
+    }
+}
+
+impl fmt::Display for AssertCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "asserts: {} passed, {} failed", self.passed, self.failed)?;
+        for (label, count) in &self.failures {
+            if *count != 0 {
+                writeln!(f, "  {}: {}", label, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// bounded, whatever the number of requests
+#[derive(Debug, Clone)]
 struct Statistics {
-    max_duration: BinaryHeap<time::Duration>,
-    min_duration: BinaryHeap<cmp::Reverse<time::Duration>>,
-    platency: PLatency,
-    request_status_count: RequestStatusCount,
+    request_count: usize,
+    max_duration: Option<time::Duration>,
+    min_duration: Option<time::Duration>,
+    latency: Latency,
+    /// durations from the intended start of the requests instead of when they were sent
+    corrected_latency: Latency,
+    /// in the order of `Phases::NAMES`, empty for the iterations
+    phase_latencies: Vec<Latency>,
+    /// see `track_duration`
+    expected_interval_correction: bool,
+    total_duration: time::Duration,
+    min_body_size: Option<u64>,
+    max_body_size: Option<u64>,
+    total_body_size: u64,
+    /// the last `kept_body_count` bodies, see `engine::ResponseBody::Keep`
+    kept_bodies: collections::VecDeque<Vec<u8>>,
+    kept_body_count: usize,
+    request_status_count: ResponseCount<usize>,
+    /// transport errors and failed captures by kind, see
+    /// `engine::Session::complete`
+    error_count: ResponseCount<&'static str>,
+    protocol_count: ResponseCount<&'static str>,
+    assert_count: AssertCount,
 }
 
 impl Statistics {
-    fn new() -> Self {
+    fn new(
+        assert_labels: Vec<String>,
+        expected_interval_correction: bool,
+        significant_digits: u8,
+        kept_body_count: usize,
+        phases: bool,
+    ) -> Self {
+        let phase_count = if phases { Phases::NAMES.len() } else { 0 };
         Self {
-            max_duration: BinaryHeap::new(),
-            min_duration: BinaryHeap::new(),
-            platency: PLatency::new(800, 0.5, 1.005),
-            request_status_count: RequestStatusCount::new(),
+            request_count: 0,
+            max_duration: None,
+            min_duration: None,
+            latency: Latency::new(significant_digits),
+            corrected_latency: Latency::new(significant_digits),
+            phase_latencies: (0..phase_count)
+                .map(|_| Latency::coarse(significant_digits))
+                .collect(),
+            expected_interval_correction,
+            total_duration: time::Duration::ZERO,
+            min_body_size: None,
+            max_body_size: None,
+            total_body_size: 0,
+            kept_bodies: collections::VecDeque::new(),
+            kept_body_count,
+            request_status_count: ResponseCount::new(),
+            error_count: ResponseCount::new(),
+            protocol_count: ResponseCount::new(),
+            assert_count: AssertCount::new(assert_labels),
         }
     }
 
     fn request_count(&self) -> usize {
-        self.max_duration.len()
+        self.request_count
     }
 
-    fn track(&mut self, response: Response) {
-        self.max_duration.push(response.duration);
-        self.min_duration.push(cmp::Reverse(response.duration));
-        self.platency.track(response.duration);
+    /// `delay` is the time between the intended start and the actual start.
+    /// Without an intended start, the requests that a slow one held back are
+    /// estimated from `expected_interval`, the mean duration of an iteration
+    /// as each one sends a request of each entry
+    fn track_duration(
+        &mut self,
+        duration: time::Duration,
+        delay: time::Duration,
+        expected_interval: Option<time::Duration>,
+    ) {
+        if let Some(expected_interval) =
+            expected_interval.filter(|_| self.expected_interval_correction)
+        {
+            self.corrected_latency
+                .track_with_expected_interval(duration, expected_interval);
+        } else {
+            self.corrected_latency.track(duration + delay);
+        }
+
+        self.request_count += 1;
+        self.max_duration = self.max_duration.max(Some(duration));
+        self.min_duration = Some(self.min_duration.map_or(duration, |v| v.min(duration)));
+        self.latency.track(duration);
+        self.total_duration += duration;
+    }
+
+    fn track(
+        &mut self,
+        response: Response,
+        delay: time::Duration,
+        expected_interval: Option<time::Duration>,
+    ) {
+        self.track_duration(response.duration, delay, expected_interval);
+        for (latency, duration) in self
+            .phase_latencies
+            .iter_mut()
+            .zip(response.phases.durations())
+        {
+            latency.track(duration);
+        }
+        self.min_body_size = Some(
+            self.min_body_size
+                .map_or(response.body_size, |v| v.min(response.body_size)),
+        );
+        self.max_body_size = self.max_body_size.max(Some(response.body_size));
+        self.total_body_size += response.body_size;
+        if let Some(body) = response.body {
+            self.keep_body(body);
+        }
         self.request_status_count.track(response.status);
+        self.protocol_count.track(response.protocol);
+        self.assert_count.track(&response.failed_asserts);
+    }
+
+    fn keep_body(&mut self, body: Vec<u8>) {
+        if self.kept_body_count == 0 {
+            return;
+        }
+        if self.kept_bodies.len() == self.kept_body_count {
+            self.kept_bodies.pop_front();
+        }
+        self.kept_bodies.push_back(body);
+    }
+
+    fn track_error(&mut self, kind: &'static str) {
+        self.error_count.track(kind);
     }
 
     fn get_max_duration(&self) -> Option<time::Duration> {
-        self.max_duration.peek().map(|v| v.clone())
+        self.max_duration
     }
 
     fn get_min_duration(&self) -> Option<time::Duration> {
-        self.min_duration.peek().map(|v| v.0.clone())
+        self.min_duration
+    }
+
+    /// back to no sample, the assert labels are kept
+    fn reset(&mut self) {
+        self.request_count = 0;
+        self.max_duration = None;
+        self.min_duration = None;
+        self.latency.reset();
+        self.corrected_latency.reset();
+        for latency in &mut self.phase_latencies {
+            latency.reset();
+        }
+        self.total_duration = time::Duration::ZERO;
+        self.min_body_size = None;
+        self.max_body_size = None;
+        self.total_body_size = 0;
+        self.kept_bodies.clear();
+        self.request_status_count.reset();
+        self.error_count.reset();
+        self.protocol_count.reset();
+        self.assert_count.reset();
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.request_count += other.request_count;
+        self.max_duration = self.max_duration.max(other.max_duration);
+        self.min_duration = match (self.min_duration, other.min_duration) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.latency.merge(&other.latency);
+        self.corrected_latency.merge(&other.corrected_latency);
+        for (latency, other_latency) in self.phase_latencies.iter_mut().zip(&other.phase_latencies)
+        {
+            latency.merge(other_latency);
+        }
+        self.total_duration += other.total_duration;
+        self.min_body_size = match (self.min_body_size, other.min_body_size) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max_body_size = self.max_body_size.max(other.max_body_size);
+        self.total_body_size += other.total_body_size;
+        for body in &other.kept_bodies {
+            self.keep_body(body.clone());
+        }
+        self.request_status_count.merge(&other.request_status_count);
+        self.error_count.merge(&other.error_count);
+        self.protocol_count.merge(&other.protocol_count);
+        self.assert_count.merge(&other.assert_count);
     }
 }
 
@@ -476,25 +1523,489 @@ impl fmt::Display for Statistics {
         let default_duration = time::Duration::from_secs(0);
         write!(
             f,
-            "max: {:.4}s\nmin: {:.4}s\np99.9: {:.4}s\np99: {:.4}s\np95: {:.4}s\np50: {:.4}s\nstatuses:\n{}",
+            "max: {:.6}s\nmin: {:.6}s\n",
             self.get_max_duration()
                 .unwrap_or(default_duration)
-                .as_secs_f32(),
+                .as_secs_f64(),
             self.get_min_duration()
                 .unwrap_or(default_duration)
-                .as_secs_f32(),
-            self.platency.p_ms(99.9) / 1000.0,
-            self.platency.p_ms(99.0) / 1000.0,
-            self.platency.p_ms(95.0) / 1000.0,
-            self.platency.p_ms(50.0) / 1000.0,
-            self.request_status_count,
-        )
+                .as_secs_f64(),
+        )?;
+        write_percentiles(f, &self.latency, &self.corrected_latency)?;
+        if !self.phase_latencies.iter().all(Latency::is_empty) {
+            write_phases(f, &self.phase_latencies)?;
+        }
+        if let (Some(min), Some(max)) = (self.min_body_size, self.max_body_size) {
+            writeln!(
+                f,
+                "body size: min {}B, max {}B, mean {}B, total {}B",
+                min,
+                max,
+                self.total_body_size / self.request_count.max(1) as u64,
+                self.total_body_size
+            )?;
+        }
+        write!(f, "statuses:\n{}", self.request_status_count)?;
+        if !self.error_count.0.is_empty() {
+            write!(f, "errors:\n{}", self.error_count)?;
+        }
+        write!(f, "protocols:\n{}", self.protocol_count)?;
+        if !self.assert_count.failures.is_empty() {
+            write!(f, "{}", self.assert_count)?;
+        }
+        Ok(())
+    }
+}
+
+const PERCENTILES: [f64; 4] = [99.9, 99.0, 95.0, 50.0];
+
+fn write_percentiles(
+    f: &mut fmt::Formatter<'_>,
+    latency: &Latency,
+    corrected_latency: &Latency,
+) -> fmt::Result {
+    for percent in PERCENTILES {
+        writeln!(
+            f,
+            "p{}: {:.6}s (corrected: {:.6}s)",
+            percent,
+            latency.percentile(percent).as_secs_f64(),
+            corrected_latency.percentile(percent).as_secs_f64()
+        )?;
+    }
+    Ok(())
+}
+
+/// a row of percentiles by phase, see `Phases`
+fn write_phases(f: &mut fmt::Formatter<'_>, phase_latencies: &[Latency]) -> fmt::Result {
+    write!(f, "{:<10}", "phases:")?;
+    for percent in PERCENTILES {
+        write!(f, " {:>10}", format!("p{}", percent))?;
+    }
+    writeln!(f)?;
+    for (name, latency) in Phases::NAMES.iter().zip(phase_latencies) {
+        write!(f, "{:<10}", format!("{}:", name))?;
+        for percent in PERCENTILES {
+            write!(f, " {:>9.6}s", latency.percentile(percent).as_secs_f64())?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct ScenarioStatistics {
+    entries: Vec<(String, Statistics)>,
+    iteration: Statistics,
+    /// `--rate`, iterations not started on time are counted
+    rate: Option<f64>,
+    late_count: usize,
+    dropped_count: usize,
+}
+
+impl ScenarioStatistics {
+    fn new(
+        entries: &[hurl_core::ast::Entry],
+        lines: &[&str],
+        rate: Option<f64>,
+        significant_digits: u8,
+        kept_body_count: usize,
+    ) -> Self {
+        Self {
+            entries: entries
+                .iter()
+                .map(|entry| {
+                    let assert_labels = assert::Check::from_entry(entry)
+                        .iter()
+                        .map(|check| check.label(lines))
+                        .collect();
+                    (
+                        entry_label(entry),
+                        Statistics::new(
+                            assert_labels,
+                            rate.is_none(),
+                            significant_digits,
+                            kept_body_count,
+                            true,
+                        ),
+                    )
+                })
+                .collect(),
+            iteration: Statistics::new(Vec::new(), rate.is_none(), significant_digits, 0, false),
+            rate,
+            late_count: 0,
+            dropped_count: 0,
+        }
+    }
+
+    fn request_count(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|(_, statistics)| statistics.request_count())
+            .sum()
+    }
+
+    /// the bodies kept by `--response-body keep=N`, by entry
+    fn write_kept_bodies(&self, w: &mut impl io::Write) -> io::Result<()> {
+        for (index, (label, statistics)) in self.entries.iter().enumerate() {
+            for body in &statistics.kept_bodies {
+                writeln!(w, "entry {} ({}):", index + 1, label)?;
+                w.write_all(body)?;
+                writeln!(w)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn iteration_count(&self) -> usize {
+        self.iteration.request_count()
+    }
+
+    /// `expected_interval` is the mean duration of the iterations so far, see
+    /// `Statistics::track_duration`
+    fn track(&mut self, sample: Sample, expected_interval: Option<time::Duration>) {
+        match sample {
+            Sample::Entry {
+                index,
+                response,
+                delay,
+            } => self.entries[index]
+                .1
+                .track(response, delay, expected_interval),
+            Sample::Iteration { duration, delay } => {
+                self.iteration
+                    .track_duration(duration, delay, expected_interval);
+                if schedule::is_late(delay) {
+                    self.late_count += 1;
+                }
+            }
+            Sample::Dropped => self.dropped_count += 1,
+            Sample::Error { index, kind } => self.entries[index].1.track_error(kind),
+        }
+    }
+
+    fn reset(&mut self) {
+        for (_, statistics) in &mut self.entries {
+            statistics.reset();
+        }
+        self.iteration.reset();
+        self.late_count = 0;
+        self.dropped_count = 0;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for ((_, statistics), (_, other_statistics)) in self.entries.iter_mut().zip(&other.entries)
+        {
+            statistics.merge(other_statistics);
+        }
+        self.iteration.merge(&other.iteration);
+        self.late_count += other.late_count;
+        self.dropped_count += other.dropped_count;
+    }
+}
+
+/// how often a worker publishes its statistics for reporting
+const PUBLISH_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+/// statistics of a worker, tracking a sample takes no lock. Every
+/// `PUBLISH_INTERVAL` they are merged into `published`, shared by the workers,
+/// and reset, so that a worker only holds the samples of an interval
+struct WorkerStatistics<'a> {
+    statistics: ScenarioStatistics,
+    published: &'a sync::Mutex<ScenarioStatistics>,
+    publish_instant: time::Instant,
+    series: series::Recorder<'a>,
+    /// number and total duration of the iterations of the worker, kept over
+    /// the resets for the expected interval of the corrected latencies
+    iterations: (u32, time::Duration),
+}
+
+impl<'a> WorkerStatistics<'a> {
+    /// `statistics` without any sample
+    fn new(
+        statistics: ScenarioStatistics,
+        published: &'a sync::Mutex<ScenarioStatistics>,
+        series: &'a series::TimeSeries,
+    ) -> Self {
+        Self {
+            statistics,
+            published,
+            publish_instant: time::Instant::now(),
+            series: series.recorder(),
+            iterations: (0, time::Duration::ZERO),
+        }
+    }
+
+    fn track(&mut self, sample: Sample) {
+        self.series.track(&sample);
+        let (iteration_count, total_duration) = self.iterations;
+        let expected_interval = (iteration_count != 0).then(|| total_duration / iteration_count);
+        if let Sample::Iteration { duration, .. } = &sample {
+            self.iterations = (iteration_count + 1, total_duration + *duration);
+        }
+        self.statistics.track(sample, expected_interval);
+        if self.publish_instant.elapsed() >= PUBLISH_INTERVAL {
+            // while another worker publishes, the samples wait for the next one
+            if let Ok(mut published) = self.published.try_lock() {
+                self.publish(&mut published);
+            }
+        }
+    }
+
+    fn publish(&mut self, published: &mut ScenarioStatistics) {
+        published.merge(&self.statistics);
+        self.statistics.reset();
+        self.publish_instant = time::Instant::now();
+    }
+
+    /// publishes the time series too, once the worker ended
+    fn finish(&mut self) {
+        let published = self.published;
+        self.publish(&mut published.lock().unwrap());
+        self.series.flush();
+    }
+}
+
+/// totals of a run over its measured window, all entries together
+struct Summary {
+    window: time::Duration,
+    requests: Statistics,
+    iteration_count: usize,
+}
+
+impl Summary {
+    fn new(statistics: &ScenarioStatistics, window: time::Duration) -> Self {
+        let mut requests = Statistics::new(
+            Vec::new(),
+            statistics.iteration.expected_interval_correction,
+            statistics.iteration.latency.0.sigfig(),
+            0,
+            true,
+        );
+        for (_, entry_statistics) in &statistics.entries {
+            requests.merge(entry_statistics);
+        }
+        Self {
+            window,
+            requests,
+            iteration_count: statistics.iteration_count(),
+        }
+    }
+
+    fn error_count(&self) -> usize {
+        self.requests.error_count.0.iter().map(|(_, n)| n).sum()
+    }
+
+    /// transport errors and failed captures out of the requests sent
+    fn error_rate(&self) -> f64 {
+        let sent_count = self.requests.request_count + self.error_count();
+        self.error_count() as f64 / sent_count.max(1) as f64
+    }
+
+    /// responses neither 2xx nor 3xx out of the responses
+    fn http_error_rate(&self) -> f64 {
+        let count: usize = self
+            .requests
+            .request_status_count
+            .0
+            .iter()
+            .filter(|(status, _)| !(200..400).contains(status))
+            .map(|(_, n)| n)
+            .sum();
+        count as f64 / self.requests.request_count.max(1) as f64
+    }
+
+    /// responses with a failed check out of the responses
+    fn assert_failure_rate(&self) -> f64 {
+        self.requests.assert_count.failed as f64 / self.requests.request_count.max(1) as f64
+    }
+
+    fn per_second(&self, count: usize) -> f64 {
+        count as f64 / self.window.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requests = &self.requests;
+        writeln!(f, "summary ({:.3}s):", self.window.as_secs_f64())?;
+        writeln!(
+            f,
+            "requests: {} ({:.1}/s)",
+            requests.request_count,
+            self.per_second(requests.request_count)
+        )?;
+        writeln!(
+            f,
+            "iterations: {} ({:.1}/s)",
+            self.iteration_count,
+            self.per_second(self.iteration_count)
+        )?;
+        writeln!(
+            f,
+            "errors: {} ({:.2}%)",
+            self.error_count(),
+            self.error_rate() * 100.0
+        )?;
+        writeln!(
+            f,
+            "mean: {:.6}s\nstddev: {:.6}s",
+            requests.latency.mean().as_secs_f64(),
+            requests.latency.stddev().as_secs_f64()
+        )?;
+        write!(f, "{}", requests)?;
+        writeln!(f, "bytes received: {}B", requests.total_body_size)
+    }
+}
+
+impl fmt::Display for ScenarioStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (label, statistics)) in self.entries.iter().enumerate() {
+            write!(f, "entry {} ({}):\n{}", index + 1, label, statistics)?;
+        }
+        if let [(_, first), rest @ ..] = self.entries.as_slice()
+            && !rest.is_empty()
+        {
+            let mut latency = first.latency.clone();
+            let mut corrected_latency = first.corrected_latency.clone();
+            for (_, statistics) in rest {
+                latency.merge(&statistics.latency);
+                corrected_latency.merge(&statistics.corrected_latency);
+            }
+            writeln!(f, "requests ({} entries):", self.entries.len())?;
+            write_percentiles(f, &latency, &corrected_latency)?;
+        }
+        write!(
+            f,
+            "iteration ({} entries):\n{}",
+            self.entries.len(),
+            self.iteration
+        )?;
+        if let Some(rate) = self.rate {
+            writeln!(
+                f,
+                "rate {}/s: {} late, {} dropped",
+                rate, self.late_count, self.dropped_count
+            )?;
+        }
+        Ok(())
     }
 }
 
 struct Response {
     duration: time::Duration,
+    body_size: u64,
+    /// with `--response-body keep=N` only
+    body: Option<Vec<u8>>,
+    phases: Phases,
     status: usize,
+    protocol: &'static str,
+    /// indices of the failed `assert::Check`s of the entry
+    failed_asserts: Vec<usize>,
+}
+
+/// `delay` is the time between the intended start and the actual start, with
+/// `--rate` every request of an iteration is as late as the iteration
+enum Sample {
+    Entry {
+        index: usize,
+        response: Response,
+        delay: time::Duration,
+    },
+    Iteration {
+        duration: time::Duration,
+        delay: time::Duration,
+    },
+    /// `--rate` slot that no worker was free to start
+    Dropped,
+    /// transport error or failed capture of the entry `index`, see
+    /// `engine::Session::complete`
+    Error { index: usize, kind: &'static str },
+}
+
+fn entry_label(entry: &hurl_core::ast::Entry) -> String {
+    format!("{} {}", entry.request.method, entry.request.url)
+}
+
+/// sections and options of the entry that are not applied to the benchmarked requests
+fn ignored_sections(entry: &hurl_core::ast::Entry) -> Vec<String> {
+    let mut sections: Vec<&hurl_core::ast::Section> = entry.request.sections.iter().collect();
+    if let Some(response) = &entry.response {
+        sections.extend(response.sections.iter());
+    }
+
+    let mut warnings = Vec::new();
+    for (index, section) in sections.iter().enumerate() {
+        if sections[..index]
+            .iter()
+            .any(|v| mem::discriminant(&v.value) == mem::discriminant(&section.value))
+        {
+            warnings.push(format!(
+                "[{}] is repeated, only the first one is used",
+                section.identifier()
+            ));
+        }
+    }
+    for option in options::unsupported(entry) {
+        warnings.push(format!("option {} is not supported", option));
+    }
+    warnings
+}
+
+/// whether the response headers and body are needed by the captures or
+/// checks of the entry
+fn collects(entry: &hurl_core::ast::Entry, checks: &[assert::Check]) -> bool {
+    let has_captures = entry
+        .response
+        .as_ref()
+        .is_some_and(|response| !response.captures().is_empty());
+    has_captures || checks.iter().any(|check| check.needs_response())
+}
+
+/// sets the captures of the entry and evaluates its checks
+fn evaluate_response(
+    template_resolver: &mut TemplateResolver,
+    entry: &hurl_core::ast::Entry,
+    checks: &[assert::Check],
+    response: &HttpResponse,
+) -> Result<Response> {
+    let captures = entry
+        .response
+        .as_ref()
+        .map(|response| response.captures())
+        .unwrap_or_default();
+    let query_resolver = query::QueryResolver::new(template_resolver, response);
+    let captured = captures
+        .iter()
+        .map(|capture| {
+            let name = template_resolver.resolve(&capture.name)?;
+            let value = query_resolver
+                .resolve_filtered(&capture.query, &capture.filters)?
+                .ok_or_else(|| anyhow!("capture {}: no value", name))?;
+            Ok((name, value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (key, value) in captured {
+        template_resolver.set_variable(key, value);
+    }
+
+    // a check that can not be evaluated, e.g. jsonpath on a html body, counts as failed
+    let failed_asserts = checks
+        .iter()
+        .enumerate()
+        .filter(|(_, check)| !check.evaluate(template_resolver, response).unwrap_or(false))
+        .map(|(index, _)| index)
+        .collect();
+
+    Ok(Response {
+        status: response.status as usize,
+        protocol: response.protocol,
+        duration: response.duration,
+        body_size: response.body_size,
+        body: None,
+        phases: response.phases,
+        failed_asserts,
+    })
 }
 
 fn main() -> Result<()> {
@@ -517,88 +2028,288 @@ fn main() -> Result<()> {
         )
     })?;
 
-    let template_resolver = TemplateResolver::new(cmd_args.variables.clone());
-    let json_resolver = JsonResolver::new(template_resolver.clone());
+    let file_root = path::Path::new(&cmd_args.filepath)
+        .parent()
+        .map(|v| v.to_path_buf())
+        .unwrap_or_default();
+    let mut template_resolver = TemplateResolver::new(cmd_args.variables.clone(), file_root);
+    template_resolver.load_files(&hurl_file.entries)?;
 
-    let endpoint = Endpoint::new(
-        &template_resolver,
-        &json_resolver,
-        hurl_file
-            .entries
-            .get(0)
-            .ok_or_else(|| anyhow!("expected hurl file to have an entry"))?,
-    )?;
-    eprintln!("endpoint: {}", &endpoint);
-
-    let (response_tx, response_rx) = sync::mpsc::channel::<Result<Response>>();
+    if hurl_file.entries.is_empty() {
+        return Err(anyhow!("expected hurl file to have an entry"));
+    }
+    for (index, entry) in hurl_file.entries.iter().enumerate() {
+        eprintln!("entry {}: {}", index + 1, entry_label(entry));
+        for warning in ignored_sections(entry) {
+            eprintln!("warning: entry {}: {}", index + 1, warning);
+        }
+    }
 
+    let empty_statistics = ScenarioStatistics::new(
+        &hurl_file.entries,
+        &file_contents.lines().collect::<Vec<_>>(),
+        cmd_args.rate,
+        cmd_args.significant_digits,
+        cmd_args.response_body.kept_body_count(),
+    );
+    let worker_count = match cmd_args.engine {
+        engine::Engine::Threads => cmd_args.parrallelism,
+        engine::Engine::Multi => cmd_args.threads.min(cmd_args.parrallelism),
+    };
+    let published = sync::Arc::new(sync::Mutex::new(empty_statistics.clone()));
+
+    let checks = hurl_file
+        .entries
+        .iter()
+        .map(assert::Check::from_entry)
+        .collect::<Vec<_>>();
+    let scenario = sync::Arc::new(engine::Scenario {
+        entries: hurl_file.entries.clone(),
+        collects: hurl_file
+            .entries
+            .iter()
+            .zip(&checks)
+            .map(|(entry, checks)| collects(entry, checks))
+            .collect(),
+        checks,
+        variables: template_resolver,
+        defaults: options::EntryOptions::from_protocol(cmd_args.protocol),
+        response_body: cmd_args.response_body,
+        schedule: cmd_args.rate.map(schedule::Schedule::new),
+    });
+    let endpoints = report::resolve_endpoints(&scenario);
+    let request_log = cmd_args
+        .log_requests
+        .as_deref()
+        .map(request_log::RequestLog::create)
+        .transpose()?;
+    let start = time::SystemTime::now();
+    let stop = sync::Arc::new(engine::Stop::new(cmd_args.max_errors));
+    let series = sync::Arc::new(series::TimeSeries::new(
+        stop.start(),
+        cmd_args.interval,
+        cmd_args.significant_digits,
+    ));
     let mut thread_handles = Vec::new();
-    for _ in 0..cmd_args.parrallelism {
+    let mut first_session = 0;
+    for index in 0..worker_count {
+        // the multi engine spreads the sessions evenly over its threads
+        let session_count = cmd_args.parrallelism / worker_count
+            + usize::from(index < cmd_args.parrallelism % worker_count);
+        let sessions = first_session..first_session + session_count;
+        first_session = sessions.end;
         thread_handles.push(thread::spawn({
-            let endpoint = endpoint.clone();
-            let response_tx = response_tx.clone();
+            let scenario = scenario.clone();
+            let stop = stop.clone();
+            let log = request_log.as_ref().map(request_log::RequestLog::logger);
+            let series = series.clone();
+            let published = published.clone();
+            let statistics = empty_statistics.clone();
             move || -> Result<()> {
-                let mut client = curl::easy::Easy::new();
-                loop {
-                    let now = time::Instant::now();
-                    let response = endpoint.send_request(&mut client).map(|v| Response {
-                        status: v as usize,
-                        duration: now.elapsed(),
-                    });
-                    if let Err(_) = response_tx.send(response) {
-                        break;
-                    }
-                }
-                Ok(())
+                engine::run_worker(
+                    cmd_args.engine,
+                    sessions,
+                    cmd_args.max_streams,
+                    &scenario,
+                    &stop,
+                    log,
+                    WorkerStatistics::new(statistics, &published, &series),
+                )
             }
         }));
     }
 
-    let statistics = sync::Arc::new(sync::Mutex::new(Statistics::new()));
-    let start_instant = time::Instant::now();
-
-    thread::spawn({
-        let statistics = statistics.clone();
-        move || -> Result<()> {
-            let mut stderr = io::stderr();
-
-            let mut prev_request_count: usize = 0;
-            let mut prev_instant = time::Instant::now();
-            loop {
-                let current_request_count = statistics.lock().unwrap().request_count();
-                let rps = ((current_request_count - prev_request_count) as f32)
-                    / prev_instant.elapsed().as_secs_f32();
-
-                prev_instant = time::Instant::now();
-                prev_request_count = current_request_count;
-                let printed_string = format!(
-                    "({:.1}/{:.1}) [{}rps]\n{}",
-                    start_instant.elapsed().as_secs_f32(),
-                    cmd_args.duration.as_secs_f32(),
-                    rps as usize,
-                    statistics.lock().unwrap()
-                );
-                write!(stderr, "{}", &printed_string)?;
-                thread::sleep(time::Duration::from_secs(1));
-                // up N lines, move cursor to first column, clear till end of screen
-                write!(stderr, "\x1b[{}A\r\x1b[0J", printed_string.lines().count())?;
-            }
-        }
-    });
-
+    let mut stderr = io::stderr();
+    let mut prev_request_count: usize = 0;
+    let mut prev_iteration_count: usize = 0;
+    let mut prev_instant = time::Instant::now();
+    let mut printed_line_count = 0;
     loop {
-        if start_instant.elapsed() > cmd_args.duration {
+        let remaining = cmd_args.duration.saturating_sub(stop.window());
+        // a worker only ends early on error, or once --max-errors is reached
+        if remaining.is_zero()
+            || stop.is_stopped()
+            || thread_handles.iter().any(|v| v.is_finished())
+        {
             break;
         }
-        let response = response_rx.recv()??;
-        statistics.lock().unwrap().track(response);
+        thread::sleep(remaining.min(time::Duration::from_secs(1)));
+        if stop.window() >= cmd_args.duration {
+            // no progress at the end, so that the stop is on time
+            break;
+        }
+
+        let statistics = published.lock().unwrap().clone();
+        let (current_request_count, current_iteration_count) =
+            (statistics.request_count(), statistics.iteration_count());
+        let elapsed = prev_instant.elapsed().as_secs_f32();
+        let rps = ((current_request_count - prev_request_count) as f32) / elapsed;
+        let ips = ((current_iteration_count - prev_iteration_count) as f32) / elapsed;
+
+        prev_instant = time::Instant::now();
+        prev_request_count = current_request_count;
+        prev_iteration_count = current_iteration_count;
+        let printed_string = format!(
+            "({:.1}/{:.1}) [{}rps, {}ips]\n{}",
+            stop.window().as_secs_f32(),
+            cmd_args.duration.as_secs_f32(),
+            rps as usize,
+            ips as usize,
+            statistics
+        );
+        if printed_line_count != 0 {
+            // up N lines, move cursor to first column, clear till end of screen
+            write!(stderr, "\x1b[{}A\r\x1b[0J", printed_line_count)?;
+        }
+        write!(stderr, "{}", &printed_string)?;
+        printed_line_count = printed_string.lines().count();
+    }
+    stop.stop();
+    if printed_line_count != 0 {
+        write!(stderr, "\x1b[{}A\r\x1b[0J", printed_line_count)?;
     }
-    drop(response_rx);
 
     eprintln!("waiting for threads to settle");
     for thread_handle in thread_handles {
         thread_handle.join().unwrap()?;
     }
+    let statistics = published.lock().unwrap().clone();
+    eprint!("{}", statistics);
+    let summary = Summary::new(&statistics, stop.window());
+    eprint!("{}", summary);
+    let outcomes = cmd_args
+        .thresholds
+        .iter()
+        .map(|threshold| threshold.evaluate(&summary))
+        .collect::<Vec<_>>();
+    if !outcomes.is_empty() {
+        eprintln!("thresholds:");
+        for outcome in &outcomes {
+            eprintln!("{}", outcome);
+        }
+    }
+    statistics.write_kept_bodies(&mut io::stdout().lock())?;
+    let report = report::Report {
+        cmd_args: &cmd_args,
+        endpoints: &endpoints,
+        start,
+        statistics: &statistics,
+        summary: &summary,
+        points: &series.points(stop.window()),
+        outcomes: &outcomes,
+    };
+    for output in &cmd_args.outputs {
+        output.write(&report)?;
+    }
+    if let Some(request_log) = request_log {
+        request_log.finish();
+    }
 
+    if stop.max_errors_reached() {
+        return Err(anyhow!(
+            "stopped early after {} errors",
+            cmd_args.max_errors.unwrap_or_default()
+        ));
+    }
+    let failed_count = outcomes.iter().filter(|outcome| !outcome.passed).count();
+    if failed_count > 0 {
+        eprintln!("{} of {} thresholds failed", failed_count, outcomes.len());
+        // not flushed by exit
+        io::stdout().flush()?;
+        process::exit(threshold::FAILED_EXIT_CODE);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_parse() {
+        let variable = Variable::parse("a=b==").unwrap();
+        assert_eq!(variable.key, "a");
+        assert_eq!(variable.value, query::Value::String("b==".to_string()));
+
+        let variable = Variable::parse("token=").unwrap();
+        assert_eq!(variable.value, query::Value::String(String::new()));
+
+        assert!(Variable::parse("a").is_err());
+    }
+
+    #[test]
+    fn variable_typed_value() {
+        assert_eq!(Variable::typed_value("1"), query::Value::Integer(1));
+        assert_eq!(Variable::typed_value("-12"), query::Value::Integer(-12));
+        assert_eq!(Variable::typed_value("1.5"), query::Value::Float(1.5));
+        assert_eq!(Variable::typed_value("true"), query::Value::Bool(true));
+        assert_eq!(Variable::typed_value("false"), query::Value::Bool(false));
+        assert_eq!(Variable::typed_value("null"), query::Value::Null);
+        assert_eq!(
+            Variable::typed_value("abc"),
+            query::Value::String("abc".to_string())
+        );
+        // quoted, a string whatever it looks like
+        assert_eq!(
+            Variable::typed_value("\"1\""),
+            query::Value::String("1".to_string())
+        );
+        assert_eq!(
+            Variable::typed_value("\"true\""),
+            query::Value::String("true".to_string())
+        );
+        assert_eq!(
+            Variable::typed_value("\"\""),
+            query::Value::String(String::new())
+        );
+        // not a JSON number
+        assert_eq!(
+            Variable::typed_value("01"),
+            query::Value::String("01".to_string())
+        );
+    }
+
+    #[test]
+    fn new_date_civil_from_days() {
+        let date = |seconds, micros| {
+            new_date(
+                time::UNIX_EPOCH
+                    + time::Duration::from_secs(seconds)
+                    + time::Duration::from_micros(micros),
+            )
+        };
+        assert_eq!(date(0, 0), "1970-01-01T00:00:00.000000Z");
+        // leap day of a year divisible by 400
+        assert_eq!(date(951827445, 7), "2000-02-29T12:30:45.000007Z");
+        assert_eq!(date(1735689599, 123456), "2024-12-31T23:59:59.123456Z");
+        // 2100 is not a leap year
+        assert_eq!(date(4107542400, 0), "2100-03-01T00:00:00.000000Z");
+        // before the epoch, clamped to it
+        assert_eq!(
+            new_date(time::UNIX_EPOCH - time::Duration::from_secs(1)),
+            "1970-01-01T00:00:00.000000Z"
+        );
+    }
+
+    #[test]
+    fn byte_reader_read() {
+        use io::Read;
+
+        let mut reader = ByteReader::new(b"hello"[..].into());
+        let mut buf = [0; 3];
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf, b"hel");
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"lo");
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        let mut reader = ByteReader::new(sync::Arc::default());
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        let mut contents = Vec::new();
+        ByteReader::new(b"hello"[..].into())
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b"hello");
+    }
+}
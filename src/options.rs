@@ -0,0 +1,469 @@
+use std::time;
+
+use anyhow::{Result, anyhow};
+
+use crate::{TemplateResolver, query::Value};
+
+/// how many times an entry is sent in a row
+#[derive(Clone, Copy)]
+pub enum Repeat {
+    Finite(usize),
+    Infinite,
+}
+
+impl Repeat {
+    /// whether the `count`th time, starting from 0, is within the limit
+    pub fn contains(self, count: usize) -> bool {
+        match self {
+            Repeat::Finite(v) => count < v,
+            Repeat::Infinite => true,
+        }
+    }
+}
+
+/// `[Options]` of an entry, resolved in order so that a `variable` option
+/// can be used by the following ones
+#[derive(Clone)]
+pub struct EntryOptions {
+    pub headers: Vec<(String, String)>,
+    pub delay: time::Duration,
+    pub retry: Option<Repeat>,
+    pub retry_interval: time::Duration,
+    pub skip: bool,
+    pub repeat: Repeat,
+    aws_sigv4: Option<String>,
+    cacert: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+    compressed: bool,
+    connect_to: Vec<String>,
+    connect_timeout: Option<time::Duration>,
+    http_version: Option<curl::easy::HttpVersion>,
+    insecure: bool,
+    ip_resolve: Option<curl::easy::IpResolve>,
+    follow_location: bool,
+    follow_location_trusted: bool,
+    limit_rate: Option<u64>,
+    max_redirect: Option<u32>,
+    max_time: Option<time::Duration>,
+    negotiate: bool,
+    netrc: Option<curl::easy::NetRc>,
+    ntlm: bool,
+    path_as_is: bool,
+    pinned_public_key: Option<String>,
+    proxy: Option<String>,
+    resolve: Vec<String>,
+    unix_socket: Option<String>,
+    user: Option<String>,
+}
+
+impl Default for EntryOptions {
+    fn default() -> Self {
+        Self {
+            headers: Vec::new(),
+            delay: time::Duration::ZERO,
+            retry: None,
+            retry_interval: time::Duration::from_millis(1000),
+            skip: false,
+            repeat: Repeat::Finite(1),
+            aws_sigv4: None,
+            cacert: None,
+            client_cert: None,
+            client_key: None,
+            compressed: false,
+            connect_to: Vec::new(),
+            connect_timeout: None,
+            http_version: None,
+            insecure: false,
+            ip_resolve: None,
+            follow_location: false,
+            follow_location_trusted: false,
+            limit_rate: None,
+            max_redirect: None,
+            max_time: None,
+            negotiate: false,
+            netrc: None,
+            ntlm: false,
+            path_as_is: false,
+            pinned_public_key: None,
+            proxy: None,
+            resolve: Vec::new(),
+            unix_socket: None,
+            user: None,
+        }
+    }
+}
+
+/// `--protocol` of the command line, `h2c` is HTTP/2 over cleartext without
+/// an upgrade from HTTP/1.1
+pub fn parse_protocol(protocol: &str) -> Result<curl::easy::HttpVersion> {
+    match protocol {
+        "http1.0" => Ok(curl::easy::HttpVersion::V10),
+        "http1.1" => Ok(curl::easy::HttpVersion::V11),
+        "http2" => Ok(curl::easy::HttpVersion::V2),
+        "h2c" => Ok(curl::easy::HttpVersion::V2PriorKnowledge),
+        _ => Err(anyhow!(
+            "protocol {}: expected http1.0, http1.1, http2 or h2c",
+            protocol
+        )),
+    }
+}
+
+/// name of a `--protocol`, see `parse_protocol`
+pub fn protocol_name(http_version: curl::easy::HttpVersion) -> &'static str {
+    match http_version {
+        curl::easy::HttpVersion::V10 => "http1.0",
+        curl::easy::HttpVersion::V11 => "http1.1",
+        curl::easy::HttpVersion::V2 => "http2",
+        curl::easy::HttpVersion::V2PriorKnowledge => "h2c",
+        _ => "negotiated",
+    }
+}
+
+/// options that have no meaning for a benchmark and are ignored
+pub fn unsupported(entry: &hurl_core::ast::Entry) -> Vec<&'static str> {
+    entry
+        .request
+        .options()
+        .iter()
+        .filter(|option| {
+            matches!(
+                option.kind,
+                hurl_core::ast::OptionKind::NetRcFile(_)
+                    | hurl_core::ast::OptionKind::Output(_)
+                    | hurl_core::ast::OptionKind::Verbose(_)
+                    | hurl_core::ast::OptionKind::VeryVerbose(_)
+            )
+        })
+        .map(|option| option.kind.identifier())
+        .collect()
+}
+
+fn resolve_boolean(
+    template_resolver: &TemplateResolver,
+    option: &hurl_core::ast::BooleanOption,
+) -> Result<bool> {
+    match option {
+        hurl_core::ast::BooleanOption::Literal(v) => Ok(*v),
+        hurl_core::ast::BooleanOption::Placeholder(placeholder) => {
+            let value = template_resolver.resolve_placeholder(placeholder)?;
+            value
+                .parse()
+                .map_err(|_| anyhow!("expected a boolean, got {}", value))
+        }
+    }
+}
+
+fn resolve_natural(
+    template_resolver: &TemplateResolver,
+    option: &hurl_core::ast::NaturalOption,
+) -> Result<u64> {
+    match option {
+        hurl_core::ast::NaturalOption::Literal(v) => Ok(v.as_u64()),
+        hurl_core::ast::NaturalOption::Placeholder(placeholder) => {
+            let value = template_resolver.resolve_placeholder(placeholder)?;
+            value
+                .parse()
+                .map_err(|_| anyhow!("expected a positive integer, got {}", value))
+        }
+    }
+}
+
+/// `-1` is infinite, as in hurl
+fn resolve_count(
+    template_resolver: &TemplateResolver,
+    option: &hurl_core::ast::CountOption,
+) -> Result<Repeat> {
+    match option {
+        hurl_core::ast::CountOption::Literal(hurl_core::types::Count::Finite(v)) => {
+            Ok(Repeat::Finite(*v))
+        }
+        hurl_core::ast::CountOption::Literal(hurl_core::types::Count::Infinite) => {
+            Ok(Repeat::Infinite)
+        }
+        hurl_core::ast::CountOption::Placeholder(placeholder) => {
+            let value = template_resolver.resolve_placeholder(placeholder)?;
+            match value.parse::<i64>() {
+                Ok(-1) => Ok(Repeat::Infinite),
+                Ok(v) if v >= 0 => Ok(Repeat::Finite(v as usize)),
+                _ => Err(anyhow!("expected a count, got {}", value)),
+            }
+        }
+    }
+}
+
+/// a value without unit is in `default_unit`, which depends on the option
+fn resolve_duration(
+    template_resolver: &TemplateResolver,
+    option: &hurl_core::ast::DurationOption,
+    default_unit: hurl_core::types::DurationUnit,
+) -> Result<time::Duration> {
+    let (value, unit) = match option {
+        hurl_core::ast::DurationOption::Literal(duration) => (
+            duration.value.as_u64(),
+            duration.unit.unwrap_or(default_unit),
+        ),
+        hurl_core::ast::DurationOption::Placeholder(placeholder) => {
+            let value = template_resolver.resolve_placeholder(placeholder)?;
+            (
+                value
+                    .parse()
+                    .map_err(|_| anyhow!("expected a duration, got {}", value))?,
+                default_unit,
+            )
+        }
+    };
+    Ok(match unit {
+        hurl_core::types::DurationUnit::MilliSecond => time::Duration::from_millis(value),
+        hurl_core::types::DurationUnit::Second => time::Duration::from_secs(value),
+        hurl_core::types::DurationUnit::Minute => time::Duration::from_secs(value * 60),
+        hurl_core::types::DurationUnit::Hour => time::Duration::from_secs(value * 3600),
+    })
+}
+
+fn resolve_variable_value(
+    template_resolver: &TemplateResolver,
+    value: &hurl_core::ast::VariableValue,
+) -> Result<Value> {
+    Ok(match value {
+        hurl_core::ast::VariableValue::Null => Value::Null,
+        hurl_core::ast::VariableValue::Bool(v) => Value::Bool(*v),
+        hurl_core::ast::VariableValue::Number(hurl_core::ast::Number::Integer(v)) => {
+            Value::Integer(v.as_i64())
+        }
+        hurl_core::ast::VariableValue::Number(hurl_core::ast::Number::Float(v)) => {
+            Value::Float(v.as_f64())
+        }
+        hurl_core::ast::VariableValue::Number(hurl_core::ast::Number::BigInteger(v)) => {
+            Value::String(v.clone())
+        }
+        hurl_core::ast::VariableValue::String(template) => {
+            Value::String(template_resolver.resolve(template)?)
+        }
+    })
+}
+
+impl EntryOptions {
+    /// options of the command line, overridden by the `[Options]` of each entry
+    pub fn from_protocol(http_version: Option<curl::easy::HttpVersion>) -> Self {
+        Self {
+            http_version,
+            ..Default::default()
+        }
+    }
+
+    /// `variable` options are stored in the template resolver, like captures
+    pub fn new(
+        defaults: &Self,
+        template_resolver: &mut TemplateResolver,
+        entry: &hurl_core::ast::Entry,
+    ) -> Result<Self> {
+        use hurl_core::{ast::OptionKind, types::DurationUnit};
+
+        let mut options = defaults.clone();
+        for option in entry.request.options() {
+            let resolver = &*template_resolver;
+            match &option.kind {
+                OptionKind::AwsSigV4(v) => options.aws_sigv4 = Some(resolver.resolve(v)?),
+                OptionKind::CaCertificate(v) => options.cacert = Some(resolver.resolve(v)?),
+                OptionKind::ClientCert(v) => options.client_cert = Some(resolver.resolve(v)?),
+                OptionKind::ClientKey(v) => options.client_key = Some(resolver.resolve(v)?),
+                OptionKind::Compressed(v) => options.compressed = resolve_boolean(resolver, v)?,
+                OptionKind::ConnectTo(v) => options.connect_to.push(resolver.resolve(v)?),
+                OptionKind::ConnectTimeout(v) => {
+                    options.connect_timeout =
+                        Some(resolve_duration(resolver, v, DurationUnit::Second)?)
+                }
+                OptionKind::Delay(v) => {
+                    options.delay = resolve_duration(resolver, v, DurationUnit::MilliSecond)?
+                }
+                OptionKind::Header(v) => {
+                    let header = resolver.resolve(v)?;
+                    let (key, value) = header
+                        .split_once(':')
+                        .ok_or_else(|| anyhow!("header option {}: expected key: value", header))?;
+                    options
+                        .headers
+                        .push((key.trim().to_string(), value.trim().to_string()));
+                }
+                OptionKind::Http10(v) => {
+                    if resolve_boolean(resolver, v)? {
+                        options.http_version = Some(curl::easy::HttpVersion::V10);
+                    }
+                }
+                OptionKind::Http11(v) => {
+                    if resolve_boolean(resolver, v)? {
+                        options.http_version = Some(curl::easy::HttpVersion::V11);
+                    }
+                }
+                OptionKind::Http2(v) => {
+                    // already HTTP/2 when h2c is set from the command line
+                    if resolve_boolean(resolver, v)?
+                        && !matches!(
+                            options.http_version,
+                            Some(curl::easy::HttpVersion::V2PriorKnowledge)
+                        )
+                    {
+                        options.http_version = Some(curl::easy::HttpVersion::V2);
+                    }
+                }
+                OptionKind::Http3(v) => {
+                    if resolve_boolean(resolver, v)? {
+                        options.http_version = Some(curl::easy::HttpVersion::V3);
+                    }
+                }
+                OptionKind::Insecure(v) => options.insecure = resolve_boolean(resolver, v)?,
+                OptionKind::IpV4(v) => {
+                    if resolve_boolean(resolver, v)? {
+                        options.ip_resolve = Some(curl::easy::IpResolve::V4);
+                    }
+                }
+                OptionKind::IpV6(v) => {
+                    if resolve_boolean(resolver, v)? {
+                        options.ip_resolve = Some(curl::easy::IpResolve::V6);
+                    }
+                }
+                OptionKind::FollowLocation(v) => {
+                    options.follow_location = resolve_boolean(resolver, v)?
+                }
+                OptionKind::FollowLocationTrusted(v) => {
+                    options.follow_location_trusted = resolve_boolean(resolver, v)?
+                }
+                OptionKind::LimitRate(v) => {
+                    options.limit_rate = Some(resolve_natural(resolver, v)?)
+                }
+                OptionKind::MaxRedirect(v) => {
+                    options.max_redirect = Some(match resolve_count(resolver, v)? {
+                        Repeat::Finite(v) => v as u32,
+                        Repeat::Infinite => u32::MAX,
+                    })
+                }
+                OptionKind::MaxTime(v) => {
+                    options.max_time = Some(resolve_duration(resolver, v, DurationUnit::Second)?)
+                }
+                OptionKind::Negotiate(v) => options.negotiate = resolve_boolean(resolver, v)?,
+                OptionKind::NetRc(v) => {
+                    if resolve_boolean(resolver, v)? {
+                        options.netrc = Some(curl::easy::NetRc::Required);
+                    }
+                }
+                OptionKind::NetRcOptional(v) => {
+                    if resolve_boolean(resolver, v)? {
+                        options.netrc = Some(curl::easy::NetRc::Optional);
+                    }
+                }
+                OptionKind::Ntlm(v) => options.ntlm = resolve_boolean(resolver, v)?,
+                OptionKind::PathAsIs(v) => options.path_as_is = resolve_boolean(resolver, v)?,
+                OptionKind::PinnedPublicKey(v) => {
+                    options.pinned_public_key = Some(resolver.resolve(v)?)
+                }
+                OptionKind::Proxy(v) => options.proxy = Some(resolver.resolve(v)?),
+                OptionKind::Repeat(v) => options.repeat = resolve_count(resolver, v)?,
+                OptionKind::Resolve(v) => options.resolve.push(resolver.resolve(v)?),
+                OptionKind::Retry(v) => options.retry = Some(resolve_count(resolver, v)?),
+                OptionKind::RetryInterval(v) => {
+                    options.retry_interval =
+                        resolve_duration(resolver, v, DurationUnit::MilliSecond)?
+                }
+                OptionKind::Skip(v) => options.skip = resolve_boolean(resolver, v)?,
+                OptionKind::UnixSocket(v) => options.unix_socket = Some(resolver.resolve(v)?),
+                OptionKind::User(v) => options.user = Some(resolver.resolve(v)?),
+                OptionKind::Variable(definition) => {
+                    let value = resolve_variable_value(resolver, &definition.value)?;
+                    template_resolver.set_variable(definition.name.clone(), value);
+                }
+                // reported by `unsupported`
+                OptionKind::NetRcFile(_)
+                | OptionKind::Output(_)
+                | OptionKind::Verbose(_)
+                | OptionKind::VeryVerbose(_) => {}
+            }
+        }
+        Ok(options)
+    }
+
+    /// sets the options on a client that has just been reset
+    pub fn apply<H>(&self, client: &mut curl::easy::Easy2<H>) -> Result<()> {
+        if let Some(v) = &self.aws_sigv4 {
+            client.aws_sigv4(v)?;
+        }
+        if let Some(v) = &self.cacert {
+            client.cainfo(v)?;
+        }
+        if let Some(v) = &self.client_cert {
+            client.ssl_cert(v)?;
+        }
+        if let Some(v) = &self.client_key {
+            client.ssl_key(v)?;
+        }
+        if self.compressed {
+            // an empty encoding offers every encoding curl can decode
+            client.accept_encoding("")?;
+        }
+        if !self.connect_to.is_empty() {
+            let mut list = curl::easy::List::new();
+            for v in &self.connect_to {
+                list.append(v)?;
+            }
+            client.connect_to(list)?;
+        }
+        if let Some(v) = self.connect_timeout {
+            client.connect_timeout(v)?;
+        }
+        if let Some(v) = self.http_version {
+            client.http_version(v)?;
+        }
+        if self.insecure {
+            client.ssl_verify_peer(false)?;
+            client.ssl_verify_host(false)?;
+        }
+        if let Some(v) = self.ip_resolve {
+            client.ip_resolve(v)?;
+        }
+        if self.follow_location || self.follow_location_trusted {
+            client.follow_location(true)?;
+            client.unrestricted_auth(self.follow_location_trusted)?;
+            // same default as hurl
+            client.max_redirections(self.max_redirect.unwrap_or(50))?;
+        }
+        if let Some(v) = self.limit_rate {
+            client.max_recv_speed(v)?;
+        }
+        if let Some(v) = self.max_time {
+            client.timeout(v)?;
+        }
+        if self.negotiate || self.ntlm {
+            let mut auth = curl::easy::Auth::new();
+            auth.gssnegotiate(self.negotiate).ntlm(self.ntlm);
+            client.http_auth(&auth)?;
+        }
+        if let Some(v) = self.netrc {
+            client.netrc(v)?;
+        }
+        if self.path_as_is {
+            client.path_as_is(true)?;
+        }
+        if let Some(v) = &self.pinned_public_key {
+            client.pinned_public_key(v)?;
+        }
+        if let Some(v) = &self.proxy {
+            client.proxy(v)?;
+        }
+        if !self.resolve.is_empty() {
+            let mut list = curl::easy::List::new();
+            for v in &self.resolve {
+                list.append(v)?;
+            }
+            client.resolve(list)?;
+        }
+        if let Some(v) = &self.unix_socket {
+            client.unix_socket(v)?;
+        }
+        if let Some(v) = &self.user {
+            let (username, password) = v.split_once(':').unwrap_or((v, ""));
+            client.username(username)?;
+            client.password(password)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,650 @@
+use std::fmt;
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+
+use crate::{HttpResponse, TemplateResolver};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Object(Vec<(String, Value)>),
+    /// xpath node set, only its size is kept
+    Nodeset(usize),
+}
+
+impl Value {
+    fn from_json(json: &serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(v) => Value::Bool(*v),
+            serde_json::Value::Number(v) => match v.as_i64() {
+                Some(v) => Value::Integer(v),
+                None => Value::Float(v.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(v) => Value::String(v.clone()),
+            serde_json::Value::Array(v) => Value::List(v.iter().map(Self::from_json).collect()),
+            serde_json::Value::Object(v) => Value::Object(
+                v.iter()
+                    .map(|(key, value)| (key.clone(), Self::from_json(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(v) => serde_json::Value::Bool(*v),
+            Value::Integer(v) => serde_json::Value::from(*v),
+            Value::Float(v) => serde_json::Value::from(*v),
+            Value::String(v) => serde_json::Value::String(v.clone()),
+            Value::Bytes(v) => serde_json::Value::String(String::from_utf8_lossy(v).to_string()),
+            Value::List(v) => serde_json::Value::Array(v.iter().map(Self::to_json).collect()),
+            Value::Object(v) => serde_json::Value::Object(
+                v.iter()
+                    .map(|(key, value)| (key.clone(), value.to_json()))
+                    .collect(),
+            ),
+            Value::Nodeset(v) => serde_json::Value::from(*v),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bytes(_) => "bytes",
+            Value::List(_) => "list",
+            Value::Object(_) => "object",
+            Value::Nodeset(_) => "nodeset",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Integer(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::String(v) => write!(f, "{}", v),
+            Value::Bytes(v) => write!(f, "{}", String::from_utf8_lossy(v)),
+            Value::List(_) | Value::Object(_) => write!(f, "{}", self.to_json()),
+            Value::Nodeset(v) => write!(f, "nodeset of size {}", v),
+        }
+    }
+}
+
+/// jsonpath expressions without wildcards, slices, filters or descendant
+/// segments select at most one node, hurl returns that node instead of a list
+fn is_singular_jsonpath(expr: &str) -> bool {
+    !["*", "..", "?", ":", ","]
+        .iter()
+        .any(|token| expr.contains(token))
+}
+
+fn eval_jsonpath(expr: &str, json: &serde_json::Value) -> Result<Option<Value>> {
+    let path = serde_json_path::JsonPath::parse(expr)
+        .map_err(|err| anyhow!("invalid jsonpath {}: {}", expr, err))?;
+    let nodes = path.query(json).all();
+
+    if is_singular_jsonpath(expr) {
+        return Ok(nodes.first().map(|v| Value::from_json(v)));
+    }
+    Ok(Some(Value::List(
+        nodes.into_iter().map(Value::from_json).collect(),
+    )))
+}
+
+fn eval_xpath(expr: &str, text: &str, html: bool) -> Result<Option<Value>> {
+    let parser = if html {
+        libxml::parser::Parser::default_html()
+    } else {
+        libxml::parser::Parser::default()
+    };
+    let document = parser
+        .parse_string(text)
+        .map_err(|err| anyhow!("invalid xml: {:?}", err))?;
+    let context = libxml::xpath::Context::new(&document)
+        .map_err(|_| anyhow!("could not create xpath context"))?;
+    let object = context
+        .evaluate(expr)
+        .map_err(|_| anyhow!("invalid xpath {}", expr))?;
+
+    // SAFETY: the object pointer is valid until `object` is dropped
+    let (kind, boolval, floatval) = unsafe {
+        (
+            (*object.ptr).type_,
+            (*object.ptr).boolval,
+            (*object.ptr).floatval,
+        )
+    };
+    Ok(match kind {
+        libxml::bindings::xmlXPathObjectType_XPATH_NODESET => {
+            Some(Value::Nodeset(object.get_number_of_nodes()))
+        }
+        libxml::bindings::xmlXPathObjectType_XPATH_BOOLEAN => Some(Value::Bool(boolval != 0)),
+        libxml::bindings::xmlXPathObjectType_XPATH_NUMBER => {
+            if floatval.fract() == 0.0 && floatval.is_finite() {
+                Some(Value::Integer(floatval as i64))
+            } else {
+                Some(Value::Float(floatval))
+            }
+        }
+        libxml::bindings::xmlXPathObjectType_XPATH_STRING => {
+            Some(Value::String(object.to_string()))
+        }
+        _ => return Err(anyhow!("xpath {} returned an unsupported type", expr)),
+    })
+}
+
+/// negative indices count from the end of the list, -1 is the last item
+fn nth(list: Vec<Value>, n: i64) -> Option<Value> {
+    let index = if n < 0 { list.len() as i64 + n } else { n };
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| list.into_iter().nth(index))
+}
+
+fn parse_json(text: &str) -> Result<serde_json::Value> {
+    serde_json::from_str(text).map_err(|err| anyhow!("invalid json: {}", err))
+}
+
+pub fn url_encode(string: &str) -> String {
+    let mut encoded = String::new();
+    for byte in string.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn url_decode(string: &str) -> Result<String> {
+    let bytes = string.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' => {
+                let hex = string
+                    .get(index + 1..index + 3)
+                    .ok_or_else(|| anyhow!("invalid percent encoding in {}", string))?;
+                decoded.push(u8::from_str_radix(hex, 16)?);
+                index += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            v => {
+                decoded.push(v);
+                index += 1;
+            }
+        }
+    }
+    Ok(String::from_utf8(decoded)?)
+}
+
+fn html_escape(string: &str) -> String {
+    string
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
+
+fn html_unescape(string: &str) -> String {
+    string
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+struct SetCookie {
+    name: String,
+    value: String,
+    attributes: Vec<(String, String)>,
+}
+
+impl SetCookie {
+    fn parse(header: &str) -> Option<Self> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let attributes = parts
+            .map(|part| match part.split_once('=') {
+                Some((key, value)) => (key.trim().to_string(), value.trim().to_string()),
+                None => (part.trim().to_string(), String::new()),
+            })
+            .collect();
+        Some(Self {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+            attributes,
+        })
+    }
+
+    fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute_key, _)| attribute_key.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct QueryResolver<'a> {
+    template_resolver: &'a TemplateResolver,
+    response: &'a HttpResponse,
+}
+
+impl<'a> QueryResolver<'a> {
+    pub fn new(template_resolver: &'a TemplateResolver, response: &'a HttpResponse) -> Self {
+        Self {
+            template_resolver,
+            response,
+        }
+    }
+
+    fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.response.body).to_string()
+    }
+
+    fn resolve_regex(&self, regex_value: &hurl_core::ast::RegexValue) -> Result<regex::Regex> {
+        match regex_value {
+            hurl_core::ast::RegexValue::Template(template) => Ok(regex::Regex::new(
+                &self.template_resolver.resolve(template)?,
+            )?),
+            hurl_core::ast::RegexValue::Regex(regex) => Ok(regex.inner.clone()),
+        }
+    }
+
+    fn resolve_cookie(&self, cookie_path: &hurl_core::ast::CookiePath) -> Result<Option<Value>> {
+        let name = self.template_resolver.resolve(&cookie_path.name)?;
+        let Some(cookie) = self
+            .response
+            .headers("set-cookie")
+            .into_iter()
+            .filter_map(SetCookie::parse)
+            .find(|cookie| cookie.name == name)
+        else {
+            return Ok(None);
+        };
+
+        let string_attribute =
+            |key: &str| cookie.attribute(key).map(|v| Value::String(v.to_string()));
+        let Some(cookie_attribute) = &cookie_path.attribute else {
+            return Ok(Some(Value::String(cookie.value)));
+        };
+        Ok(match &cookie_attribute.name {
+            hurl_core::ast::CookieAttributeName::Value(_) => {
+                Some(Value::String(cookie.value.clone()))
+            }
+            hurl_core::ast::CookieAttributeName::Expires(_) => string_attribute("Expires"),
+            hurl_core::ast::CookieAttributeName::MaxAge(_) => cookie
+                .attribute("Max-Age")
+                .map(|v| v.parse().map(Value::Integer))
+                .transpose()?,
+            hurl_core::ast::CookieAttributeName::Domain(_) => string_attribute("Domain"),
+            hurl_core::ast::CookieAttributeName::Path(_) => string_attribute("Path"),
+            hurl_core::ast::CookieAttributeName::SameSite(_) => string_attribute("SameSite"),
+            hurl_core::ast::CookieAttributeName::Secure(_) => {
+                Some(Value::Bool(cookie.attribute("Secure").is_some()))
+            }
+            hurl_core::ast::CookieAttributeName::HttpOnly(_) => {
+                Some(Value::Bool(cookie.attribute("HttpOnly").is_some()))
+            }
+        })
+    }
+
+    /// Ok(None) means the query did not match anything in the response
+    pub fn resolve(&self, query: &hurl_core::ast::Query) -> Result<Option<Value>> {
+        Ok(match &query.value {
+            hurl_core::ast::QueryValue::Status => Some(Value::Integer(self.response.status as i64)),
+            hurl_core::ast::QueryValue::Version => Some(Value::String(
+                self.response
+                    .version
+                    .trim_start_matches("HTTP/")
+                    .to_string(),
+            )),
+            hurl_core::ast::QueryValue::Url => Some(Value::String(self.response.url.clone())),
+            hurl_core::ast::QueryValue::Header { name, .. } => {
+                let name = self.template_resolver.resolve(name)?;
+                let values = self.response.headers(&name);
+                match values.len() {
+                    0 => None,
+                    1 => Some(Value::String(values[0].to_string())),
+                    _ => Some(Value::List(
+                        values
+                            .into_iter()
+                            .map(|v| Value::String(v.to_string()))
+                            .collect(),
+                    )),
+                }
+            }
+            hurl_core::ast::QueryValue::Cookie { expr, .. } => self.resolve_cookie(expr)?,
+            hurl_core::ast::QueryValue::Body => Some(Value::String(self.body_text())),
+            hurl_core::ast::QueryValue::Jsonpath { expr, .. } => {
+                let expr = self.template_resolver.resolve(expr)?;
+                eval_jsonpath(&expr, &parse_json(&self.body_text())?)?
+            }
+            hurl_core::ast::QueryValue::Xpath { expr, .. } => {
+                let expr = self.template_resolver.resolve(expr)?;
+                let html = self
+                    .response
+                    .headers("content-type")
+                    .first()
+                    .is_some_and(|v| v.contains("html"));
+                eval_xpath(&expr, &self.body_text(), html)?
+            }
+            hurl_core::ast::QueryValue::Regex { value, .. } => self
+                .resolve_regex(value)?
+                .captures(&self.body_text())
+                .and_then(|captures| captures.get(1))
+                .map(|v| Value::String(v.as_str().to_string())),
+            hurl_core::ast::QueryValue::Variable { name, .. } => {
+                let name = self.template_resolver.resolve(name)?;
+                self.template_resolver.get_variable(&name).cloned()
+            }
+            hurl_core::ast::QueryValue::Duration => {
+                Some(Value::Integer(self.response.duration.as_millis() as i64))
+            }
+            hurl_core::ast::QueryValue::Bytes => Some(Value::Bytes(self.response.body.clone())),
+            v => return Err(anyhow!("{} query is not supported", v.identifier())),
+        })
+    }
+
+    fn apply_filter(
+        &self,
+        value: Value,
+        filter: &hurl_core::ast::FilterValue,
+    ) -> Result<Option<Value>> {
+        Ok(match (filter, value) {
+            (hurl_core::ast::FilterValue::Count, Value::List(v)) => {
+                Some(Value::Integer(v.len() as i64))
+            }
+            (hurl_core::ast::FilterValue::Count, Value::Bytes(v)) => {
+                Some(Value::Integer(v.len() as i64))
+            }
+            (hurl_core::ast::FilterValue::Count, Value::Nodeset(v)) => {
+                Some(Value::Integer(v as i64))
+            }
+            (hurl_core::ast::FilterValue::First, Value::List(v)) => v.into_iter().next(),
+            (hurl_core::ast::FilterValue::Last, Value::List(v)) => v.into_iter().last(),
+            (hurl_core::ast::FilterValue::Nth { n, .. }, Value::List(v)) => {
+                let n: i64 = match n {
+                    hurl_core::ast::IntegerValue::Literal(n) => n.as_i64(),
+                    hurl_core::ast::IntegerValue::Placeholder(placeholder) => self
+                        .template_resolver
+                        .resolve_placeholder(placeholder)?
+                        .parse()?,
+                };
+                nth(v, n)
+            }
+            (hurl_core::ast::FilterValue::Regex { value: regex, .. }, Value::String(v)) => self
+                .resolve_regex(regex)?
+                .captures(&v)
+                .and_then(|captures| captures.get(1))
+                .map(|v| Value::String(v.as_str().to_string())),
+            (
+                hurl_core::ast::FilterValue::Replace {
+                    old_value,
+                    new_value,
+                    ..
+                },
+                Value::String(v),
+            ) => Some(Value::String(v.replace(
+                &self.template_resolver.resolve(old_value)?,
+                &self.template_resolver.resolve(new_value)?,
+            ))),
+            (
+                hurl_core::ast::FilterValue::ReplaceRegex {
+                    pattern, new_value, ..
+                },
+                Value::String(v),
+            ) => Some(Value::String(
+                self.resolve_regex(pattern)?
+                    .replace_all(&v, self.template_resolver.resolve(new_value)?.as_str())
+                    .to_string(),
+            )),
+            (hurl_core::ast::FilterValue::Split { sep, .. }, Value::String(v)) => {
+                let sep = self.template_resolver.resolve(sep)?;
+                Some(Value::List(
+                    v.split(sep.as_str())
+                        .map(|v| Value::String(v.to_string()))
+                        .collect(),
+                ))
+            }
+            (hurl_core::ast::FilterValue::JsonPath { expr, .. }, Value::String(v)) => {
+                eval_jsonpath(&self.template_resolver.resolve(expr)?, &parse_json(&v)?)?
+            }
+            (hurl_core::ast::FilterValue::XPath { expr, .. }, Value::String(v)) => {
+                eval_xpath(&self.template_resolver.resolve(expr)?, &v, false)?
+            }
+            (hurl_core::ast::FilterValue::ToInt, Value::String(v)) => {
+                Some(Value::Integer(v.trim().parse()?))
+            }
+            (hurl_core::ast::FilterValue::ToInt, Value::Float(v)) => Some(Value::Integer(v as i64)),
+            (hurl_core::ast::FilterValue::ToInt, v @ Value::Integer(_)) => Some(v),
+            (hurl_core::ast::FilterValue::ToFloat, Value::String(v)) => {
+                Some(Value::Float(v.trim().parse()?))
+            }
+            (hurl_core::ast::FilterValue::ToFloat, Value::Integer(v)) => {
+                Some(Value::Float(v as f64))
+            }
+            (hurl_core::ast::FilterValue::ToFloat, v @ Value::Float(_)) => Some(v),
+            (hurl_core::ast::FilterValue::ToString, v) => Some(Value::String(v.to_string())),
+            (hurl_core::ast::FilterValue::UrlEncode, Value::String(v)) => {
+                Some(Value::String(url_encode(&v)))
+            }
+            (hurl_core::ast::FilterValue::UrlDecode, Value::String(v)) => {
+                Some(Value::String(url_decode(&v)?))
+            }
+            (hurl_core::ast::FilterValue::UrlQueryParam { param, .. }, Value::String(v)) => {
+                let param = self.template_resolver.resolve(param)?;
+                let query = v.split_once('?').map(|(_, query)| query).unwrap_or("");
+                query
+                    .split('&')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| url_decode(key).is_ok_and(|key| key == param))
+                    .map(|(_, value)| url_decode(value).map(Value::String))
+                    .transpose()?
+            }
+            (hurl_core::ast::FilterValue::HtmlEscape, Value::String(v)) => {
+                Some(Value::String(html_escape(&v)))
+            }
+            (hurl_core::ast::FilterValue::HtmlUnescape, Value::String(v)) => {
+                Some(Value::String(html_unescape(&v)))
+            }
+            (hurl_core::ast::FilterValue::Base64Encode, Value::Bytes(v)) => Some(Value::String(
+                base64::engine::general_purpose::STANDARD.encode(v),
+            )),
+            (hurl_core::ast::FilterValue::Base64Decode, Value::String(v)) => Some(Value::Bytes(
+                base64::engine::general_purpose::STANDARD.decode(v)?,
+            )),
+            (hurl_core::ast::FilterValue::Base64UrlSafeEncode, Value::Bytes(v)) => Some(
+                Value::String(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(v)),
+            ),
+            (hurl_core::ast::FilterValue::Base64UrlSafeDecode, Value::String(v)) => {
+                Some(Value::Bytes(
+                    base64::engine::general_purpose::URL_SAFE_NO_PAD
+                        .decode(v.trim_end_matches('='))?,
+                ))
+            }
+            (hurl_core::ast::FilterValue::ToHex, Value::Bytes(v)) => Some(Value::String(
+                v.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            )),
+            (hurl_core::ast::FilterValue::Utf8Decode, Value::Bytes(v)) => {
+                Some(Value::String(String::from_utf8(v)?))
+            }
+            (hurl_core::ast::FilterValue::Utf8Encode, Value::String(v)) => {
+                Some(Value::Bytes(v.into_bytes()))
+            }
+            (hurl_core::ast::FilterValue::Decode { encoding, .. }, Value::Bytes(v)) => {
+                let encoding = self.template_resolver.resolve(encoding)?;
+                if !encoding.eq_ignore_ascii_case("utf-8") && !encoding.eq_ignore_ascii_case("utf8")
+                {
+                    return Err(anyhow!("decode: encoding {} is not supported", encoding));
+                }
+                Some(Value::String(String::from_utf8(v)?))
+            }
+            (
+                filter @ (hurl_core::ast::FilterValue::Count
+                | hurl_core::ast::FilterValue::First
+                | hurl_core::ast::FilterValue::Last
+                | hurl_core::ast::FilterValue::Nth { .. }
+                | hurl_core::ast::FilterValue::Regex { .. }
+                | hurl_core::ast::FilterValue::Replace { .. }
+                | hurl_core::ast::FilterValue::ReplaceRegex { .. }
+                | hurl_core::ast::FilterValue::Split { .. }
+                | hurl_core::ast::FilterValue::JsonPath { .. }
+                | hurl_core::ast::FilterValue::XPath { .. }
+                | hurl_core::ast::FilterValue::ToInt
+                | hurl_core::ast::FilterValue::ToFloat
+                | hurl_core::ast::FilterValue::UrlEncode
+                | hurl_core::ast::FilterValue::UrlDecode
+                | hurl_core::ast::FilterValue::UrlQueryParam { .. }
+                | hurl_core::ast::FilterValue::HtmlEscape
+                | hurl_core::ast::FilterValue::HtmlUnescape
+                | hurl_core::ast::FilterValue::Base64Encode
+                | hurl_core::ast::FilterValue::Base64Decode
+                | hurl_core::ast::FilterValue::Base64UrlSafeEncode
+                | hurl_core::ast::FilterValue::Base64UrlSafeDecode
+                | hurl_core::ast::FilterValue::ToHex
+                | hurl_core::ast::FilterValue::Utf8Decode
+                | hurl_core::ast::FilterValue::Utf8Encode
+                | hurl_core::ast::FilterValue::Decode { .. }),
+                v,
+            ) => {
+                return Err(anyhow!(
+                    "{} filter can not be applied to {}",
+                    filter.identifier(),
+                    v.type_name()
+                ));
+            }
+            (filter, _) => {
+                return Err(anyhow!("{} filter is not supported", filter.identifier()));
+            }
+        })
+    }
+
+    /// resolves the query and pipes the result through the filters,
+    /// Ok(None) means the query or one of the filters did not match
+    pub fn resolve_filtered(
+        &self,
+        query: &hurl_core::ast::Query,
+        filters: &[(hurl_core::ast::Whitespace, hurl_core::ast::Filter)],
+    ) -> Result<Option<Value>> {
+        let mut value = self.resolve(query)?;
+        for (_, filter) in filters {
+            let Some(v) = value else {
+                return Ok(None);
+            };
+            value = self.apply_filter(v, &filter.value)?;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn singular_jsonpath() {
+        assert!(is_singular_jsonpath("$.name"));
+        assert!(is_singular_jsonpath("$.items[0].id"));
+        assert!(is_singular_jsonpath("$['key']"));
+
+        assert!(!is_singular_jsonpath("$.items[*].id"));
+        assert!(!is_singular_jsonpath("$..id"));
+        assert!(!is_singular_jsonpath("$.items[?@.id > 1]"));
+        assert!(!is_singular_jsonpath("$.items[1:3]"));
+        assert!(!is_singular_jsonpath("$.items[0,1]"));
+    }
+
+    #[test]
+    fn jsonpath() {
+        let json = serde_json::json!({"name": "a", "items": [{"id": 1}, {"id": 2.5}]});
+        assert_eq!(
+            eval_jsonpath("$.name", &json).unwrap(),
+            Some(Value::String("a".to_string()))
+        );
+        assert_eq!(eval_jsonpath("$.missing", &json).unwrap(), None);
+        assert_eq!(
+            eval_jsonpath("$.items[*].id", &json).unwrap(),
+            Some(Value::List(vec![Value::Integer(1), Value::Float(2.5)]))
+        );
+        assert_eq!(
+            eval_jsonpath("$.items[*].missing", &json).unwrap(),
+            Some(Value::List(vec![]))
+        );
+        assert!(eval_jsonpath("$.[", &json).is_err());
+    }
+
+    #[test]
+    fn nth_index() {
+        let list = || (0..3).map(Value::Integer).collect::<Vec<_>>();
+        assert_eq!(nth(list(), 0), Some(Value::Integer(0)));
+        assert_eq!(nth(list(), 2), Some(Value::Integer(2)));
+        assert_eq!(nth(list(), 3), None);
+        assert_eq!(nth(list(), -1), Some(Value::Integer(2)));
+        assert_eq!(nth(list(), -3), Some(Value::Integer(0)));
+        assert_eq!(nth(list(), -4), None);
+        assert_eq!(nth(vec![], -1), None);
+    }
+
+    #[test]
+    fn url_encoding() {
+        assert_eq!(url_encode("a-z_0.9~"), "a-z_0.9~");
+        assert_eq!(url_encode("a b&c=é"), "a%20b%26c%3D%C3%A9");
+
+        assert_eq!(url_decode("a%20b%26c%3d%C3%A9").unwrap(), "a b&c=é");
+        assert_eq!(url_decode("a+b").unwrap(), "a b");
+        assert_eq!(url_decode("100%25").unwrap(), "100%");
+        assert!(url_decode("a%2").is_err());
+        assert!(url_decode("a%zz").is_err());
+        assert!(url_decode("%FF").is_err());
+    }
+
+    #[test]
+    fn html_escaping() {
+        let string = r#"<a href="x">'b' & c</a>"#;
+        assert_eq!(
+            html_escape(string),
+            "&lt;a href=&quot;x&quot;&gt;&#x27;b&#x27; &amp; c&lt;/a&gt;"
+        );
+        assert_eq!(html_unescape(&html_escape(string)), string);
+        assert_eq!(html_unescape("&amp;lt;"), "&lt;");
+    }
+
+    #[test]
+    fn set_cookie() {
+        let cookie =
+            SetCookie::parse("session=abc=1; Path=/; Max-Age=60; secure; HttpOnly").unwrap();
+        assert_eq!(cookie.name, "session");
+        assert_eq!(cookie.value, "abc=1");
+        assert_eq!(cookie.attribute("path"), Some("/"));
+        assert_eq!(cookie.attribute("Max-Age"), Some("60"));
+        assert_eq!(cookie.attribute("Secure"), Some(""));
+        assert_eq!(cookie.attribute("Domain"), None);
+        assert!(SetCookie::parse("invalid").is_none());
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    fs,
+    io::{self, Write},
+    time,
+};
+
+use anyhow::{Result, anyhow};
+use serde_json::json;
+
+use crate::{
+    CmdArgs, Endpoint, Latency, PERCENTILES, Phases, ResponseCount, ScenarioStatistics, Statistics,
+    Summary, engine, html, new_date, options, series, threshold,
+};
+
+/// bumped on any change other than an addition
+const JSON_REPORT_VERSION: u32 = 1;
+
+/// headers whose values are credentials, left out of the reports like the
+/// values of the variables
+const SECRET_HEADERS: [&str; 3] = ["authorization", "cookie", "proxy-authorization"];
+
+/// `--output`, written once the run ended, to stdout without a path
+pub enum Output {
+    Json {
+        path: Option<String>,
+    },
+    /// CSV of the time series
+    Intervals {
+        path: Option<String>,
+    },
+    /// `--report-html`
+    Html {
+        path: String,
+    },
+}
+
+impl Output {
+    pub fn is_stdout(&self) -> bool {
+        matches!(
+            self,
+            Self::Json { path: None } | Self::Intervals { path: None }
+        )
+    }
+
+    /// `<FORMAT>` or `<FORMAT>=<PATH>`
+    pub fn parse(output: &str) -> Result<Self> {
+        let (format, path) = match output.split_once('=') {
+            Some((format, path)) if !path.is_empty() => (format, Some(path.to_string())),
+            Some(_) => return Err(anyhow!("output {}: missing path", output)),
+            None => (output, None),
+        };
+        match format {
+            "json" => Ok(Self::Json { path }),
+            "intervals" => Ok(Self::Intervals { path }),
+            _ => Err(anyhow!("output {}: expected json or intervals", output)),
+        }
+    }
+
+    pub fn write(&self, report: &Report) -> Result<()> {
+        let (contents, path) = match self {
+            Self::Json { path } => (
+                serde_json::to_string_pretty(&report.to_json())? + "\n",
+                path.as_ref(),
+            ),
+            Self::Intervals { path } => {
+                let mut contents = Vec::new();
+                report.write_intervals(&mut contents)?;
+                (String::from_utf8(contents)?, path.as_ref())
+            }
+            Self::Html { path } => (html::render(report)?, Some(path)),
+        };
+        match path {
+            Some(path) => {
+                fs::write(path, contents).map_err(|err| anyhow!("output {}: {}", path, err))?
+            }
+            None => print!("{}", contents),
+        }
+        Ok(())
+    }
+}
+
+/// what is known of a run once it ended
+pub struct Report<'a> {
+    pub cmd_args: &'a CmdArgs,
+    /// see `resolve_endpoints`
+    pub endpoints: &'a [Option<Endpoint>],
+    pub start: time::SystemTime,
+    pub statistics: &'a ScenarioStatistics,
+    pub summary: &'a Summary,
+    pub points: &'a [series::Point],
+    pub outcomes: &'a [threshold::Outcome<'a>],
+}
+
+/// requests of the entries resolved with the variables of the command line,
+/// `None` for an entry that depends on captures
+pub fn resolve_endpoints(scenario: &engine::Scenario) -> Vec<Option<Endpoint>> {
+    let mut template_resolver = scenario.variables.clone();
+    scenario
+        .entries
+        .iter()
+        .map(|entry| {
+            let entry_options =
+                options::EntryOptions::new(&scenario.defaults, &mut template_resolver, entry)
+                    .ok()?;
+            Endpoint::new(&template_resolver, entry, &entry_options).ok()
+        })
+        .collect()
+}
+
+fn latency_json(latency: &Latency) -> serde_json::Value {
+    PERCENTILES
+        .iter()
+        .map(|percent| {
+            (
+                format!("p{}", percent),
+                json!(latency.percentile(*percent).as_secs_f64()),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn count_json<K: ToString>(count: &ResponseCount<K>) -> serde_json::Value {
+    count
+        .0
+        .iter()
+        .map(|(key, n)| (key.to_string(), json!(n)))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// durations in seconds, sizes in bytes
+fn statistics_json(statistics: &Statistics) -> serde_json::Value {
+    let seconds = |duration: Option<time::Duration>| duration.map(|v| v.as_secs_f64());
+    json!({
+        "requests": statistics.request_count(),
+        "min": seconds(statistics.get_min_duration()),
+        "max": seconds(statistics.get_max_duration()),
+        "mean": statistics.latency.mean().as_secs_f64(),
+        "stddev": statistics.latency.stddev().as_secs_f64(),
+        "percentiles": latency_json(&statistics.latency),
+        "corrected_percentiles": latency_json(&statistics.corrected_latency),
+        "phases": Phases::NAMES
+            .iter()
+            .zip(&statistics.phase_latencies)
+            .map(|(name, latency)| (name.to_string(), latency_json(latency)))
+            .collect::<serde_json::Map<_, _>>(),
+        "statuses": count_json(&statistics.request_status_count),
+        "errors": count_json(&statistics.error_count),
+        "protocols": count_json(&statistics.protocol_count),
+        "asserts": {
+            "passed": statistics.assert_count.passed,
+            "failed": statistics.assert_count.failed,
+            "failures": statistics
+                .assert_count
+                .failures
+                .iter()
+                .map(|(label, count)| json!({ "label": label, "count": count }))
+                .collect::<Vec<_>>(),
+        },
+        "body_size": {
+            "min": statistics.min_body_size,
+            "max": statistics.max_body_size,
+            "total": statistics.total_body_size,
+        },
+    })
+}
+
+fn endpoint_json(endpoint: &Endpoint) -> serde_json::Value {
+    json!({
+        "method": endpoint.method,
+        "url": endpoint.url,
+        "headers": endpoint
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let secret = SECRET_HEADERS.contains(&name.to_ascii_lowercase().as_str());
+                json!({ "name": name, "value": if secret { "<redacted>" } else { value } })
+            })
+            .collect::<Vec<_>>(),
+        "body_size": endpoint.body.as_ref().map(|body| body.len()),
+    })
+}
+
+fn point_json(start: time::SystemTime, point: &series::Point) -> serde_json::Value {
+    let mut json = json!({
+        "timestamp": new_date(start + point.offset),
+        "offset": point.offset.as_secs_f64(),
+        "duration": point.duration.as_secs_f64(),
+        "requests": point.interval.request_count,
+        "requests_per_second": point.per_second(),
+        "errors": point.interval.error_count,
+    });
+    for (name, latency) in point.latencies().into_iter().flatten() {
+        json[name] = json!(latency.as_secs_f64());
+    }
+    json
+}
+
+impl Report<'_> {
+    /// the command line, without the values of the variables that may be
+    /// secrets
+    pub fn config_json(&self) -> serde_json::Value {
+        let cmd_args = self.cmd_args;
+        json!({
+            "filepath": cmd_args.filepath,
+            "duration": cmd_args.duration.as_secs_f64(),
+            "parallelism": cmd_args.parrallelism,
+            "engine": cmd_args.engine.to_string(),
+            "threads": cmd_args.threads,
+            "max_streams": cmd_args.max_streams,
+            "max_errors": cmd_args.max_errors,
+            "protocol": cmd_args.protocol.map(options::protocol_name),
+            "rate": cmd_args.rate,
+            "significant_digits": cmd_args.significant_digits,
+            "interval": cmd_args.interval.as_secs_f64(),
+            "thresholds": cmd_args
+                .thresholds
+                .iter()
+                .map(|threshold| threshold.to_string())
+                .collect::<Vec<_>>(),
+            "response_body": cmd_args.response_body.to_string(),
+            "variables": cmd_args
+                .variables
+                .iter()
+                .map(|variable| variable.key.as_str())
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// CSV, a row by interval
+    fn write_intervals(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "timestamp,offset,duration,requests,requests_per_second,errors,p50,p90,p99,max"
+        )?;
+        for point in self.points {
+            write!(
+                w,
+                "{},{},{},{},{},{}",
+                new_date(self.start + point.offset),
+                point.offset.as_secs_f64(),
+                point.duration.as_secs_f64(),
+                point.interval.request_count,
+                point.per_second(),
+                point.interval.error_count,
+            )?;
+            match point.latencies() {
+                Some(latencies) => {
+                    for (_, latency) in latencies {
+                        write!(w, ",{}", latency.as_secs_f64())?;
+                    }
+                }
+                None => write!(w, ",,,,")?,
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let summary = self.summary;
+        let statistics = self.statistics;
+        json!({
+            "version": JSON_REPORT_VERSION,
+            "config": self.config_json(),
+            "start": new_date(self.start),
+            "end": new_date(self.start + summary.window),
+            "duration": summary.window.as_secs_f64(),
+            "summary": {
+                "requests": summary.requests.request_count(),
+                "requests_per_second": summary.per_second(summary.requests.request_count()),
+                "iterations": summary.iteration_count,
+                "iterations_per_second": summary.per_second(summary.iteration_count),
+                "errors": summary.error_count(),
+                "error_rate": summary.error_rate(),
+                "http_error_rate": summary.http_error_rate(),
+                "assert_failure_rate": summary.assert_failure_rate(),
+                "bytes_received": summary.requests.total_body_size,
+                "statistics": statistics_json(&summary.requests),
+            },
+            "entries": statistics
+                .entries
+                .iter()
+                .zip(self.endpoints)
+                .enumerate()
+                .map(|(index, ((label, entry_statistics), endpoint))| {
+                    json!({
+                        "index": index + 1,
+                        "label": label,
+                        "endpoint": endpoint.as_ref().map(endpoint_json),
+                        "statistics": statistics_json(entry_statistics),
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "iterations": statistics_json(&statistics.iteration),
+            // values in seconds for the latencies, as a fraction for the rates
+            "thresholds": self
+                .outcomes
+                .iter()
+                .map(|outcome| {
+                    json!({
+                        "threshold": outcome.threshold.to_string(),
+                        "value": outcome.value,
+                        "passed": outcome.passed,
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "intervals": self
+                .points
+                .iter()
+                .map(|point| point_json(self.start, point))
+                .collect::<Vec<_>>(),
+            "rate": statistics.rate.map(|rate| json!({
+                "rate": rate,
+                "late": statistics.late_count,
+                "dropped": statistics.dropped_count,
+            })),
+        })
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    ffi, fs,
+    io::{self, Write},
+    os, ptr,
+    sync::{self, atomic, mpsc},
+    thread, time,
+};
+
+use anyhow::{Result, anyhow};
+use serde_json::json;
+
+use crate::{Collector, Phases, new_date};
+
+/// records waiting for the writer, more are dropped rather than buffered
+const QUEUE_CAPACITY: usize = 64 * 1024;
+
+/// not exported by curl-sys
+const CURLINFO_EFFECTIVE_METHOD: curl_sys::CURLINFO = curl_sys::CURLINFO_STRING + 58;
+
+/// of `--log-requests`, from the extension of its path
+#[derive(Clone, Copy)]
+enum Format {
+    Csv,
+    Ndjson,
+}
+
+/// a request as sent, a retry is another one
+struct Record {
+    /// when it was sent
+    timestamp: time::SystemTime,
+    worker: usize,
+    entry: usize,
+    method: String,
+    url: String,
+    /// none on a transport error
+    status: Option<u32>,
+    error: Option<&'static str>,
+    /// since it was sent, as in the latency percentiles
+    duration: time::Duration,
+    /// none on a transport error, curl's timings stop anywhere then
+    phases: Option<Phases>,
+    bytes_in: u64,
+    bytes_out: u64,
+}
+
+/// method of the last request of a client
+fn effective_method<H>(client: &curl::easy::Easy2<H>) -> Result<String> {
+    let mut method: *const os::raw::c_char = ptr::null();
+    let code = unsafe {
+        curl_sys::curl_easy_getinfo(client.raw(), CURLINFO_EFFECTIVE_METHOD, &mut method)
+    };
+    if code != curl_sys::CURLE_OK {
+        return Err(curl::Error::new(code).into());
+    }
+    if method.is_null() {
+        return Ok(String::new());
+    }
+    Ok(unsafe { ffi::CStr::from_ptr(method) }
+        .to_string_lossy()
+        .into_owned())
+}
+
+/// quoted when needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl Record {
+    fn write(&self, w: &mut impl Write, format: Format) -> io::Result<()> {
+        let timings = self.phases.as_ref().map(Phases::durations);
+        match format {
+            Format::Csv => {
+                write!(
+                    w,
+                    "{},{},{},{},{},{},{},{}",
+                    new_date(self.timestamp),
+                    self.worker,
+                    self.entry,
+                    csv_field(&self.method),
+                    csv_field(&self.url),
+                    self.status.map(|v| v.to_string()).unwrap_or_default(),
+                    self.error.unwrap_or_default(),
+                    self.duration.as_secs_f64(),
+                )?;
+                for index in 0..Phases::NAMES.len() {
+                    match timings {
+                        Some(timings) => write!(w, ",{}", timings[index].as_secs_f64())?,
+                        None => write!(w, ",")?,
+                    }
+                }
+                writeln!(w, ",{},{}", self.bytes_in, self.bytes_out)
+            }
+            Format::Ndjson => {
+                let phases = timings.map(|timings| {
+                    Phases::NAMES
+                        .iter()
+                        .zip(timings)
+                        .map(|(name, timing)| (name.to_string(), json!(timing.as_secs_f64())))
+                        .collect::<serde_json::Map<_, _>>()
+                });
+                let record = json!({
+                    "timestamp": new_date(self.timestamp),
+                    "worker": self.worker,
+                    "entry": self.entry,
+                    "method": self.method,
+                    "url": self.url,
+                    "status": self.status,
+                    "error": self.error,
+                    "duration": self.duration.as_secs_f64(),
+                    "phases": phases,
+                    "bytes_in": self.bytes_in,
+                    "bytes_out": self.bytes_out,
+                });
+                writeln!(w, "{}", record)
+            }
+        }
+    }
+}
+
+/// records that were not written, reported once the run ended
+#[derive(Default)]
+struct Losses {
+    /// the writer fell behind
+    dropped: atomic::AtomicUsize,
+    /// curl could not tell about the request
+    failed: atomic::AtomicUsize,
+    first_failure: sync::OnceLock<String>,
+}
+
+/// `--log-requests`, written by a thread of its own so that the workers only
+/// pass it the records
+pub struct RequestLog {
+    sender: mpsc::SyncSender<Record>,
+    writer: thread::JoinHandle<Result<()>>,
+    losses: sync::Arc<Losses>,
+}
+
+impl RequestLog {
+    /// CSV for a `.csv` path, NDJSON otherwise
+    pub fn create(path: &str) -> Result<Self> {
+        let format = if path.ends_with(".csv") {
+            Format::Csv
+        } else {
+            Format::Ndjson
+        };
+        let file =
+            fs::File::create(path).map_err(|err| anyhow!("log requests {}: {}", path, err))?;
+        let (sender, receiver) = mpsc::sync_channel::<Record>(QUEUE_CAPACITY);
+        let writer = thread::spawn(move || -> Result<()> {
+            let mut w = io::BufWriter::new(file);
+            if let Format::Csv = format {
+                write!(w, "timestamp,worker,entry,method,url,status,error,duration")?;
+                for name in Phases::NAMES {
+                    write!(w, ",{}", name)?;
+                }
+                writeln!(w, ",bytes_in,bytes_out")?;
+            }
+            for record in receiver {
+                record.write(&mut w, format)?;
+            }
+            w.flush()?;
+            Ok(())
+        });
+        Ok(Self {
+            sender,
+            writer,
+            losses: sync::Arc::default(),
+        })
+    }
+
+    pub fn logger(&self) -> Logger {
+        Logger {
+            worker: 0,
+            sender: self.sender.clone(),
+            losses: self.losses.clone(),
+        }
+    }
+
+    /// waits for the records to be written, once the workers ended. The
+    /// run is measured whatever happens to its log, so losses are warnings
+    pub fn finish(self) {
+        drop(self.sender);
+        if let Err(err) = self.writer.join().unwrap() {
+            eprintln!("warning: request log: writing stopped: {}", err);
+        }
+        let dropped = self.losses.dropped.load(atomic::Ordering::Relaxed);
+        if dropped > 0 {
+            eprintln!(
+                "warning: request log: {} records dropped, the writer fell behind or stopped",
+                dropped
+            );
+        }
+        let failed = self.losses.failed.load(atomic::Ordering::Relaxed);
+        if let Some(failure) = self.losses.first_failure.get() {
+            eprintln!(
+                "warning: request log: {} records not written: {}",
+                failed, failure
+            );
+        }
+    }
+}
+
+/// the end of `RequestLog` given to a worker
+#[derive(Clone)]
+pub struct Logger {
+    worker: usize,
+    sender: mpsc::SyncSender<Record>,
+    losses: sync::Arc<Losses>,
+}
+
+impl Logger {
+    /// for the worker numbered `worker`
+    pub fn with_worker(&self, worker: usize) -> Self {
+        Self {
+            worker,
+            sender: self.sender.clone(),
+            losses: self.losses.clone(),
+        }
+    }
+
+    /// the last request of a client, `duration` since it was sent. Without
+    /// blocking nor failing the worker, what is not written is counted
+    pub fn log(
+        &self,
+        client: &mut curl::easy::Easy2<Collector>,
+        entry: usize,
+        duration: time::Duration,
+        error: Option<&'static str>,
+    ) {
+        match self.record(client, entry, duration, error) {
+            Ok(record) => {
+                // full, or disconnected from a writer that failed, which
+                // `RequestLog::finish` reports
+                if self.sender.try_send(record).is_err() {
+                    self.losses.dropped.fetch_add(1, atomic::Ordering::Relaxed);
+                }
+            }
+            Err(err) => {
+                self.losses.failed.fetch_add(1, atomic::Ordering::Relaxed);
+                self.losses.first_failure.get_or_init(|| err.to_string());
+            }
+        }
+    }
+
+    fn record(
+        &self,
+        client: &mut curl::easy::Easy2<Collector>,
+        entry: usize,
+        duration: time::Duration,
+        error: Option<&'static str>,
+    ) -> Result<Record> {
+        let status = Some(client.response_code()?).filter(|status| *status != 0);
+        Ok(Record {
+            timestamp: time::SystemTime::now() - duration,
+            worker: self.worker,
+            entry,
+            method: effective_method(client)?,
+            url: client.effective_url()?.unwrap_or_default().to_string(),
+            status,
+            error,
+            duration,
+            phases: match status {
+                Some(_) => Some(Phases::new(client)?),
+                None => None,
+            },
+            bytes_in: client.get_ref().body_size,
+            bytes_out: client.upload_size()? as u64,
+        })
+    }
+}
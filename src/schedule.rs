@@ -0,0 +1,109 @@
+use std::{sync::atomic, time};
+
+use anyhow::{Result, anyhow};
+
+/// starting later than this is reported as late, below it is scheduling noise
+const LATE_THRESHOLD: time::Duration = time::Duration::from_millis(1);
+
+/// open model: iterations start at fixed intervals, whatever the response
+/// times, each one run by the first worker of the pool to be free
+pub struct Schedule {
+    start: time::Instant,
+    interval: time::Duration,
+    next_slot: atomic::AtomicU64,
+}
+
+pub enum Slot {
+    /// intended start of the iteration, before or at the time it is started
+    Started { due: time::Instant },
+    /// no worker was free before the next slot was due
+    Dropped,
+}
+
+/// `delay` since the intended start, see `Slot::Started`
+pub fn is_late(delay: time::Duration) -> bool {
+    delay > LATE_THRESHOLD
+}
+
+/// `N/s`, or `N` alone, as iterations per second
+pub fn parse_rate(rate: &str) -> Result<f64> {
+    let count = rate.strip_suffix("/s").unwrap_or(rate);
+    match count.parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        _ => Err(anyhow!(
+            "rate {}: expected a positive number per second, e.g. 100/s",
+            rate
+        )),
+    }
+}
+
+impl Schedule {
+    pub fn new(rate: f64) -> Self {
+        Self {
+            start: time::Instant::now(),
+            interval: time::Duration::from_secs_f64(1.0 / rate),
+            next_slot: atomic::AtomicU64::new(0),
+        }
+    }
+
+    /// claims the next slot, the iteration is to be started once it is due
+    pub fn claim(&self) -> Slot {
+        self.claim_at(time::Instant::now())
+    }
+
+    fn claim_at(&self, now: time::Instant) -> Slot {
+        let slot = self.next_slot.fetch_add(1, atomic::Ordering::Relaxed);
+        let due = self.start + self.interval.mul_f64(slot as f64);
+        if now >= due + self.interval {
+            return Slot::Dropped;
+        }
+        Slot::Started { due }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claim_started() {
+        let schedule = Schedule::new(1.0);
+        let start = schedule.start;
+        assert!(matches!(schedule.claim_at(start), Slot::Started { due } if due == start));
+        // due in a second, started once it is due
+        assert!(matches!(
+            schedule.claim_at(start),
+            Slot::Started { due } if due == start + time::Duration::from_secs(1)
+        ));
+    }
+
+    #[test]
+    fn claim_dropped() {
+        // 10 slots of 100ms are over, the 11th is due
+        let schedule = Schedule::new(10.0);
+        let now = schedule.start + time::Duration::from_millis(1050);
+        for _ in 0..10 {
+            assert!(matches!(schedule.claim_at(now), Slot::Dropped));
+        }
+        assert!(matches!(schedule.claim_at(now), Slot::Started { .. }));
+        // the end of a slot drops it
+        let now = schedule.start + time::Duration::from_millis(1200);
+        assert!(matches!(schedule.claim_at(now), Slot::Dropped));
+    }
+
+    #[test]
+    fn late() {
+        assert!(!is_late(time::Duration::ZERO));
+        assert!(!is_late(LATE_THRESHOLD));
+        assert!(is_late(LATE_THRESHOLD + time::Duration::from_micros(1)));
+    }
+
+    #[test]
+    fn rate() {
+        assert_eq!(parse_rate("100/s").unwrap(), 100.0);
+        assert_eq!(parse_rate("0.5").unwrap(), 0.5);
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("-1/s").is_err());
+        assert!(parse_rate("100/m").is_err());
+    }
+}
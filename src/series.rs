@@ -0,0 +1,156 @@
+use std::{sync, time};
+
+use crate::{Latency, Sample};
+
+/// `--interval`, aggregates of the requests completed in each interval of the
+/// measured window, all entries together
+pub struct TimeSeries {
+    start: time::Instant,
+    interval: time::Duration,
+    significant_digits: u8,
+    intervals: sync::Mutex<Vec<Interval>>,
+}
+
+#[derive(Clone)]
+pub struct Interval {
+    pub request_count: usize,
+    /// transport errors and failed captures
+    pub error_count: usize,
+    pub latency: Latency,
+}
+
+/// an interval once the run ended
+pub struct Point {
+    /// since the start of the measured window
+    pub offset: time::Duration,
+    /// shorter than `--interval` for the last one
+    pub duration: time::Duration,
+    pub interval: Interval,
+}
+
+impl Interval {
+    fn new(significant_digits: u8) -> Self {
+        Self {
+            request_count: 0,
+            error_count: 0,
+            latency: Latency::coarse(significant_digits),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.request_count == 0 && self.error_count == 0
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.request_count += other.request_count;
+        self.error_count += other.error_count;
+        self.latency.merge(&other.latency);
+    }
+}
+
+impl Point {
+    pub fn per_second(&self) -> f64 {
+        self.interval.request_count as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// none without a response
+    pub fn latencies(&self) -> Option<[(&'static str, time::Duration); 4]> {
+        let latency = &self.interval.latency;
+        if latency.is_empty() {
+            return None;
+        }
+        Some([
+            ("p50", latency.percentile(50.0)),
+            ("p90", latency.percentile(90.0)),
+            ("p99", latency.percentile(99.0)),
+            ("max", time::Duration::from_micros(latency.0.max())),
+        ])
+    }
+}
+
+impl TimeSeries {
+    /// `start` of the measured window
+    pub fn new(start: time::Instant, interval: time::Duration, significant_digits: u8) -> Self {
+        Self {
+            start,
+            interval,
+            significant_digits,
+            intervals: sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn recorder(&self) -> Recorder<'_> {
+        Recorder {
+            series: self,
+            index: 0,
+            interval: Interval::new(self.significant_digits),
+        }
+    }
+
+    /// every interval of `window`, the empty ones included
+    pub fn points(&self, window: time::Duration) -> Vec<Point> {
+        let mut intervals = self.intervals.lock().unwrap().clone();
+        // to the millisecond of `--duration`, the run stops a little after it
+        let count = (window.as_millis() * 1_000_000).div_ceil(self.interval.as_nanos()) as usize;
+        // responses tracked as the run stopped
+        if count > 0 && intervals.len() > count {
+            for extra in intervals.split_off(count) {
+                intervals[count - 1].merge(&extra);
+            }
+        }
+        intervals.resize_with(count, || Interval::new(self.significant_digits));
+        intervals
+            .into_iter()
+            .enumerate()
+            .map(|(index, interval)| {
+                let offset = self.interval * index as u32;
+                Point {
+                    offset,
+                    duration: self.interval.min(window - offset),
+                    interval,
+                }
+            })
+            .collect()
+    }
+}
+
+/// current interval of a worker, merged into the series once the next one
+/// started so that tracking a sample takes no lock
+pub struct Recorder<'a> {
+    series: &'a TimeSeries,
+    index: usize,
+    interval: Interval,
+}
+
+impl Recorder<'_> {
+    pub fn track(&mut self, sample: &Sample) {
+        let index =
+            (self.series.start.elapsed().as_nanos() / self.series.interval.as_nanos()) as usize;
+        if index != self.index {
+            self.flush();
+            self.index = index;
+        }
+        match sample {
+            Sample::Entry { response, .. } => {
+                self.interval.request_count += 1;
+                self.interval.latency.track(response.duration);
+            }
+            Sample::Error { .. } => self.interval.error_count += 1,
+            Sample::Iteration { .. } | Sample::Dropped => {}
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.interval.is_empty() {
+            return;
+        }
+        let mut intervals = self.series.intervals.lock().unwrap();
+        if intervals.len() <= self.index {
+            intervals.resize_with(self.index + 1, || {
+                Interval::new(self.series.significant_digits)
+            });
+        }
+        intervals[self.index].merge(&self.interval);
+        self.interval = Interval::new(self.series.significant_digits);
+    }
+}
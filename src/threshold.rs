@@ -0,0 +1,273 @@
+use std::fmt;
+
+use anyhow::{Result, anyhow};
+
+use crate::Summary;
+
+/// exit status of a run that failed a threshold, errors exit with 1
+pub const FAILED_EXIT_CODE: i32 = 2;
+
+/// `--threshold`, a limit on a metric of the summary such as `p99<250ms`
+pub struct Threshold {
+    text: String,
+    metric: Metric,
+    comparison: Comparison,
+    /// in seconds for the latencies, as a fraction for the rates
+    limit: f64,
+}
+
+#[derive(Clone, Copy)]
+enum Metric {
+    Percentile(f64),
+    Mean,
+    Max,
+    /// requests per second
+    Rps,
+    /// transport errors and failed captures
+    ErrorRate,
+    /// statuses neither 2xx nor 3xx
+    HttpErrorRate,
+    AssertFailureRate,
+}
+
+#[derive(Clone, Copy)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// a threshold evaluated against the summary of a run
+pub struct Outcome<'a> {
+    pub threshold: &'a Threshold,
+    /// none for a latency or a response rate without any response, which
+    /// fails
+    pub value: Option<f64>,
+    pub passed: bool,
+}
+
+impl Metric {
+    fn parse(metric: &str) -> Option<Self> {
+        match metric {
+            "mean" => Some(Self::Mean),
+            "max" => Some(Self::Max),
+            "rps" => Some(Self::Rps),
+            "error_rate" => Some(Self::ErrorRate),
+            "http_error_rate" => Some(Self::HttpErrorRate),
+            "assert_failure_rate" => Some(Self::AssertFailureRate),
+            _ => match metric.strip_prefix('p')?.parse::<f64>() {
+                Ok(percent) if percent > 0.0 && percent <= 100.0 => Some(Self::Percentile(percent)),
+                _ => None,
+            },
+        }
+    }
+
+    fn is_latency(self) -> bool {
+        matches!(self, Self::Percentile(_) | Self::Mean | Self::Max)
+    }
+
+    fn is_rate(self) -> bool {
+        matches!(
+            self,
+            Self::ErrorRate | Self::HttpErrorRate | Self::AssertFailureRate
+        )
+    }
+
+    /// a latency with a unit, a rate in percent or as a fraction, a number
+    /// of requests per second
+    fn parse_limit(self, limit: &str) -> Option<f64> {
+        let (number, scale) = if self.is_latency() {
+            [("us", 1e-6), ("ms", 1e-3), ("s", 1.0)]
+                .into_iter()
+                .find_map(|(unit, scale)| Some((limit.strip_suffix(unit)?, scale)))?
+        } else {
+            match limit.strip_suffix('%') {
+                Some(percent) if self.is_rate() => (percent, 0.01),
+                _ => (limit.strip_suffix("/s").unwrap_or(limit), 1.0),
+            }
+        };
+        match number.trim().parse::<f64>() {
+            Ok(v) if v >= 0.0 && v.is_finite() => Some(v * scale),
+            _ => None,
+        }
+    }
+
+    fn value(self, summary: &Summary) -> Option<f64> {
+        let latency = &summary.requests.latency;
+        match self {
+            Self::Percentile(_) | Self::Mean if latency.is_empty() => None,
+            Self::Percentile(percent) => Some(latency.percentile(percent).as_secs_f64()),
+            Self::Mean => Some(latency.mean().as_secs_f64()),
+            Self::Max => Some(summary.requests.get_max_duration()?.as_secs_f64()),
+            Self::Rps => Some(summary.per_second(summary.requests.request_count)),
+            Self::ErrorRate => Some(summary.error_rate()),
+            // none without a response, like the latencies
+            Self::HttpErrorRate | Self::AssertFailureRate
+                if summary.requests.request_count == 0 =>
+            {
+                None
+            }
+            Self::HttpErrorRate => Some(summary.http_error_rate()),
+            Self::AssertFailureRate => Some(summary.assert_failure_rate()),
+        }
+    }
+}
+
+impl Comparison {
+    fn holds(self, value: f64, limit: f64) -> bool {
+        match self {
+            Self::Less => value < limit,
+            Self::LessOrEqual => value <= limit,
+            Self::Greater => value > limit,
+            Self::GreaterOrEqual => value >= limit,
+        }
+    }
+}
+
+impl Threshold {
+    /// `<METRIC><COMPARISON><LIMIT>`, see the usage
+    pub fn parse(threshold: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "threshold {}: expected <METRIC><OPERATOR><LIMIT>, e.g. p99<250ms, \
+                 error_rate<1% or rps>500",
+                threshold
+            )
+        };
+        let (index, operator) = threshold
+            .match_indices(['<', '>'])
+            .next()
+            .ok_or_else(invalid)?;
+        let (metric, rest) = (&threshold[..index], &threshold[index + 1..]);
+        let (comparison, limit) = match (operator, rest.strip_prefix('=')) {
+            ("<", Some(limit)) => (Comparison::LessOrEqual, limit),
+            ("<", None) => (Comparison::Less, rest),
+            (_, Some(limit)) => (Comparison::GreaterOrEqual, limit),
+            (_, None) => (Comparison::Greater, rest),
+        };
+        let metric = Metric::parse(metric.trim()).ok_or_else(|| {
+            anyhow!(
+                "threshold {}: unknown metric {}, expected pN, mean, max, rps, error_rate, \
+                 http_error_rate or assert_failure_rate",
+                threshold,
+                metric.trim()
+            )
+        })?;
+        let limit = metric.parse_limit(limit.trim()).ok_or_else(invalid)?;
+        Ok(Self {
+            text: threshold.to_string(),
+            metric,
+            comparison,
+            limit,
+        })
+    }
+
+    pub fn evaluate(&self, summary: &Summary) -> Outcome<'_> {
+        let value = self.metric.value(summary);
+        Outcome {
+            threshold: self,
+            value,
+            passed: value.is_some_and(|value| self.comparison.holds(value, self.limit)),
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl Outcome<'_> {
+    /// pass or fail, and the value
+    pub fn result(&self) -> String {
+        let value = match (self.value, self.threshold.metric) {
+            (None, _) => "no response".to_string(),
+            (Some(value), metric) if metric.is_rate() => format!("{:.2}%", value * 100.0),
+            (Some(value), Metric::Rps) => format!("{:.1}/s", value),
+            (Some(value), _) => format!("{:.3}ms", value * 1000.0),
+        };
+        format!("{} ({})", if self.passed { "pass" } else { "fail" }, value)
+    }
+}
+
+impl fmt::Display for Outcome<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.threshold, self.result())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_limit(threshold: &Threshold, limit: f64) {
+        assert!(
+            (threshold.limit - limit).abs() < 1e-12,
+            "{} != {}",
+            threshold.limit,
+            limit
+        );
+    }
+
+    #[test]
+    fn parse_latency() {
+        let threshold = Threshold::parse("p99<250ms").unwrap();
+        assert!(matches!(threshold.metric, Metric::Percentile(v) if v == 99.0));
+        assert!(matches!(threshold.comparison, Comparison::Less));
+        assert_limit(&threshold, 0.25);
+
+        assert_limit(&Threshold::parse("mean<1s").unwrap(), 1.0);
+        assert_limit(&Threshold::parse("max < 1500us").unwrap(), 0.0015);
+        assert!(matches!(
+            Threshold::parse("p99.9<1s").unwrap().metric,
+            Metric::Percentile(v) if v == 99.9
+        ));
+    }
+
+    #[test]
+    fn parse_rate() {
+        let threshold = Threshold::parse("error_rate<1%").unwrap();
+        assert!(matches!(threshold.metric, Metric::ErrorRate));
+        assert_limit(&threshold, 0.01);
+
+        assert_limit(&Threshold::parse("error_rate<0.05").unwrap(), 0.05);
+        assert_limit(&Threshold::parse("http_error_rate<=2.5%").unwrap(), 0.025);
+        assert!(matches!(
+            Threshold::parse("assert_failure_rate<1%").unwrap().metric,
+            Metric::AssertFailureRate
+        ));
+        assert_limit(&Threshold::parse("rps>500").unwrap(), 500.0);
+        assert_limit(&Threshold::parse("rps>500/s").unwrap(), 500.0);
+    }
+
+    #[test]
+    fn parse_comparison() {
+        let comparison = |threshold| Threshold::parse(threshold).unwrap().comparison;
+        assert!(matches!(comparison("p99<1s"), Comparison::Less));
+        assert!(matches!(comparison("p99<=1s"), Comparison::LessOrEqual));
+        assert!(matches!(comparison("rps>1"), Comparison::Greater));
+        assert!(matches!(comparison("rps>=1"), Comparison::GreaterOrEqual));
+
+        assert!(Comparison::LessOrEqual.holds(1.0, 1.0));
+        assert!(!Comparison::Less.holds(1.0, 1.0));
+        assert!(Comparison::GreaterOrEqual.holds(1.0, 1.0));
+        assert!(!Comparison::Greater.holds(1.0, 1.0));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        // a latency needs a unit
+        assert!(Threshold::parse("p99<250").is_err());
+        // a percentage is only for a rate
+        assert!(Threshold::parse("p99<1%").is_err());
+        assert!(Threshold::parse("rps>1%").is_err());
+        assert!(Threshold::parse("p0<1s").is_err());
+        assert!(Threshold::parse("p101<1s").is_err());
+        assert!(Threshold::parse("latency<1s").is_err());
+        assert!(Threshold::parse("p99").is_err());
+        assert!(Threshold::parse("p99<").is_err());
+        assert!(Threshold::parse("error_rate<-1%").is_err());
+    }
+}